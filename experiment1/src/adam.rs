@@ -0,0 +1,562 @@
+use crate::error::{MadError, MadResult};
+use crate::{ContentItem, DocumentMetadata, FixedClock, MadDocument};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+// Conversion between MadDocument and the ADAM agent-document JSON format
+// described by agent-document-schema.json (vendored at the crate root).
+pub const ADAM_FORMAT: &str = "agent-document";
+pub const ADAM_VERSION: &str = "1.0.0";
+
+const ADAM_SCHEMA: &str = include_str!("../agent-document-schema.json");
+
+const SECTION_TYPES: &[&str] = &[
+    "paragraph", "heading", "list", "code", "quote", "image",
+    "table", "math", "figure", "diagram", "chart", "custom",
+];
+
+// Graph relationships used for section relationships
+const HAS_CHILD: &str = "HAS_CHILD";
+const DEPENDS_ON: &str = "DEPENDS_ON";
+const NEXT: &str = "NEXT";
+const RELATED_TO: &str = "RELATED_TO";
+const SIMILAR_TO: &str = "SIMILAR_TO";
+
+// Content item metadata keys that carry section fields through a round trip
+const SECTION_TYPE_KEY: &str = "section_type";
+const LEVEL_KEY: &str = "level";
+const TITLE_KEY: &str = "title";
+const EXTRA_KEY: &str = "adam_extra";
+
+#[derive(Serialize, Deserialize)]
+pub struct AdamDocument {
+    pub format: String,
+    pub version: String,
+    pub metadata: AdamMetadata,
+    pub sections: Vec<AdamSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdamMetadata {
+    pub title: String,
+    pub created: String,
+    pub modified: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<AdamAuthor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdamAuthor {
+    pub name: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdamSection {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub section_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub content: Value,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "AdamRelationships::is_empty")]
+    pub relationships: AdamRelationships,
+    // image, table and any other section fields we don't model directly
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AdamRelationships {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_section: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_section: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub semantic_similarity: Vec<AdamSimilarity>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdamSimilarity {
+    pub section_id: String,
+    pub similarity_score: f64,
+}
+
+impl AdamRelationships {
+    fn is_empty(&self) -> bool {
+        self.parent.is_none()
+            && self.children.is_empty()
+            && self.dependencies.is_empty()
+            && self.previous_section.is_none()
+            && self.next_section.is_none()
+            && self.related.is_empty()
+            && self.semantic_similarity.is_empty()
+    }
+}
+
+pub fn validate(value: &Value) -> MadResult<()> {
+    static VALIDATOR: OnceLock<jsonschema::Validator> = OnceLock::new();
+    let validator = VALIDATOR.get_or_init(|| {
+        let schema: Value = serde_json::from_str(ADAM_SCHEMA).expect("bundled ADAM schema is valid JSON");
+        jsonschema::draft7::new(&schema).expect("bundled ADAM schema compiles")
+    });
+
+    let errors: Vec<String> = validator.iter_errors(value)
+        .take(5)
+        .map(|e| format!("{} at '{}'", e, e.instance_path))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(MadError::InvalidInput(format!("ADAM schema validation failed: {}", errors.join("; "))))
+    }
+}
+
+pub fn import(adam_json: &str) -> MadResult<MadDocument> {
+    let value: Value = serde_json::from_str(adam_json)?;
+    validate(&value)?;
+    let adam: AdamDocument = serde_json::from_value(value)?;
+
+    let authors: Vec<&str> = adam.metadata.authors.iter().map(|a| a.name.as_str()).collect();
+    let created = parse_timestamp(&adam.metadata.created)?;
+    let mut doc = MadDocument::with_clock(&adam.metadata.title, &authors.join(", "), &FixedClock(created));
+    // The joined author string can't be split back safely (names may contain
+    // commas), so several authors are also kept as a list
+    if authors.len() > 1 {
+        doc.metadata.authors = authors.iter().map(|name| name.to_string()).collect();
+    }
+    doc.metadata.modified = Some(parse_timestamp(&adam.metadata.modified)?);
+    if let Some(version) = adam.metadata.version {
+        doc.metadata.version = version;
+    }
+    if let Some(custom) = adam.metadata.extra.get("custom") {
+        if let Some(id) = custom.get("mad_document_id").and_then(Value::as_str) {
+            doc.metadata.id = id.to_string();
+        }
+        doc.metadata.embedding_model = custom.get("embedding_model")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        doc.metadata.embedding_dimension = custom.get("embedding_dimension")
+            .and_then(Value::as_u64)
+            .map(|dimension| dimension as usize);
+    }
+
+    for section in &adam.sections {
+        let item = section_to_item(section)?;
+        let properties = serde_json::json!({
+            "content_type": item.content_type,
+            "section_type": section.section_type,
+        });
+        let text = item.text_content.clone();
+        doc.add_content_item(item)?;
+        doc.graph_db.add_node(&section.id, "Content", &properties.to_string())?;
+
+        if let Some(embedding) = section.metadata.get("embedding") {
+            let embedding: Vec<f32> = embedding.as_array()
+                .and_then(|values| values.iter().map(|x| x.as_f64().map(|x| x as f32)).collect())
+                .ok_or_else(|| MadError::InvalidInput(format!(
+                    "Section '{}' has an embedding that is not an array of numbers", section.id
+                )))?;
+            doc.add_vector_embedding(&section.id, &text, &embedding)?;
+        }
+    }
+
+    // Both ends of a link may be listed (parent/children, previous/next), so add each edge once
+    let mut seen = HashSet::new();
+    for section in &adam.sections {
+        let rels = &section.relationships;
+        let mut links: Vec<(&str, &str, &str, String)> = Vec::new();
+
+        if let Some(parent) = &rels.parent {
+            links.push((parent, &section.id, HAS_CHILD, String::new()));
+        }
+        for child in &rels.children {
+            links.push((&section.id, child, HAS_CHILD, String::new()));
+        }
+        for dependency in &rels.dependencies {
+            links.push((&section.id, dependency, DEPENDS_ON, String::new()));
+        }
+        if let Some(previous) = &rels.previous_section {
+            links.push((previous, &section.id, NEXT, String::new()));
+        }
+        if let Some(next) = &rels.next_section {
+            links.push((&section.id, next, NEXT, String::new()));
+        }
+        for related in &rels.related {
+            links.push((&section.id, related, RELATED_TO, String::new()));
+        }
+        for similar in &rels.semantic_similarity {
+            let properties = serde_json::json!({ "score": similar.similarity_score });
+            links.push((&section.id, &similar.section_id, SIMILAR_TO, properties.to_string()));
+        }
+
+        for (from, to, relationship, properties) in links {
+            if seen.insert((from.to_string(), to.to_string(), relationship)) {
+                doc.graph_db.add_edge(from, to, relationship, &properties)?;
+            }
+        }
+    }
+
+    doc.calculate_content_hash();
+    Ok(doc)
+}
+
+pub fn export(doc: &MadDocument) -> MadResult<String> {
+    let meta = doc.metadata();
+    let created = format_timestamp(meta.created)?;
+    let modified = format_timestamp(meta.modified.unwrap_or(meta.created))?;
+
+    let mut custom = Map::new();
+    custom.insert("mad_document_id".to_string(), Value::from(meta.id.clone()));
+    if !meta.content_hash.is_empty() {
+        custom.insert("content_hash".to_string(), Value::from(meta.content_hash.clone()));
+    }
+    if let Some(model) = &meta.embedding_model {
+        custom.insert("embedding_model".to_string(), Value::from(model.clone()));
+    }
+    if let Some(dimension) = meta.embedding_dimension {
+        custom.insert("embedding_dimension".to_string(), Value::from(dimension));
+    }
+    let mut metadata_extra = Map::new();
+    metadata_extra.insert("custom".to_string(), Value::Object(custom));
+
+    let metadata = AdamMetadata {
+        title: meta.title.clone(),
+        created,
+        modified,
+        authors: document_authors(meta).into_iter()
+            .map(|name| AdamAuthor { name: name.to_string(), extra: Map::new() })
+            .collect(),
+        version: Some(meta.version.clone()),
+        extra: metadata_extra,
+    };
+
+    let mut sections: Vec<AdamSection> = doc.content_items().iter()
+        .map(|item| item_to_section(doc, item))
+        .collect::<MadResult<_>>()?;
+    add_relationships(doc, &mut sections);
+
+    let mut by_type: Map<String, Value> = Map::new();
+    for section in &sections {
+        let ids = by_type.entry(section.section_type.clone()).or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(ids) = ids {
+            ids.push(Value::from(section.id.clone()));
+        }
+    }
+
+    let adam = AdamDocument {
+        format: ADAM_FORMAT.to_string(),
+        version: ADAM_VERSION.to_string(),
+        metadata,
+        sections,
+        index: Some(serde_json::json!({ "sections_by_type": by_type })),
+    };
+
+    let value = serde_json::to_value(&adam)?;
+    validate(&value)?;
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+fn section_to_item(section: &AdamSection) -> MadResult<ContentItem> {
+    let mut metadata = HashMap::new();
+    metadata.insert(SECTION_TYPE_KEY.to_string(), section.section_type.clone());
+    if let Some(level) = section.level {
+        metadata.insert(LEVEL_KEY.to_string(), level.to_string());
+    }
+    if let Some(title) = &section.title {
+        metadata.insert(TITLE_KEY.to_string(), title.clone());
+    }
+
+    let image_src = section.extra.get("image")
+        .and_then(|image| image.get("src"))
+        .and_then(Value::as_str);
+    let content_src = match &section.content {
+        Value::Object(content) if content.get("type").and_then(Value::as_str) == Some("image") => {
+            content.get("data").and_then(|data| data.get("src").or(Some(data))).and_then(Value::as_str)
+        }
+        _ => None,
+    };
+    let image = content_src.or(image_src).and_then(decode_data_uri);
+
+    // Keep everything we don't map onto the item so export can restore it
+    let mut extra = section.extra.clone();
+    if image.is_some() {
+        if let Some(Value::Object(image)) = extra.get_mut("image") {
+            image.remove("src");
+        }
+    }
+    let mut section_metadata = section.metadata.clone();
+    section_metadata.remove("embedding");
+    if !section_metadata.is_empty() {
+        extra.insert("metadata".to_string(), Value::Object(section_metadata));
+    }
+    if !extra.is_empty() {
+        metadata.insert(EXTRA_KEY.to_string(), Value::Object(extra).to_string());
+    }
+
+    let (content_type, data, text_content) = match image {
+        Some((mime, data)) => (mime, data, image_text(section)),
+        None => match &section.content {
+            Value::String(text) => ("text/plain".to_string(), BASE64.encode(text), text.clone()),
+            content => {
+                let mut text = Vec::new();
+                collect_strings(content.get("data").unwrap_or(content), &mut text);
+                ("application/json".to_string(), BASE64.encode(content.to_string()), text.join(" "))
+            }
+        },
+    };
+
+    Ok(ContentItem {
+        id: section.id.clone(),
+        content_type,
+        data,
+        text_content,
+        metadata,
+    })
+}
+
+fn item_to_section(doc: &MadDocument, item: &ContentItem) -> MadResult<AdamSection> {
+    let mut extra: Map<String, Value> = match item.metadata.get(EXTRA_KEY) {
+        Some(json) => serde_json::from_str(json)?,
+        None => Map::new(),
+    };
+    let mut metadata = match extra.remove("metadata") {
+        Some(Value::Object(metadata)) => metadata,
+        _ => Map::new(),
+    };
+
+    let is_image = item.content_type.starts_with("image/");
+    let section_type = item.metadata.get(SECTION_TYPE_KEY)
+        .filter(|t| SECTION_TYPES.contains(&t.as_str()))
+        .cloned()
+        .unwrap_or_else(|| if is_image { "image" } else { "paragraph" }.to_string());
+
+    // image.src is a oneOf of two string schemas that both match any string, so
+    // schema-valid exports carry image bytes in a structured "image" content object
+    let content = if is_image {
+        if let Some(Value::Object(image)) = extra.get_mut("image") {
+            image.remove("src");
+        }
+        serde_json::json!({
+            "type": "image",
+            "data": {
+                "src": format!("data:{};base64,{}", item.content_type, item.data),
+                "mime_type": item.content_type,
+            }
+        })
+    } else {
+        let structured = (item.content_type == "application/json")
+            .then(|| BASE64.decode(&item.data).ok())
+            .flatten()
+            .and_then(|data| serde_json::from_slice::<Value>(&data).ok())
+            .filter(|value| value.get("type").is_some() && value.get("data").is_some());
+        structured.unwrap_or_else(|| Value::from(crate::export::item_text(item)))
+    };
+
+    if let Some(vector) = doc.vectors().iter().find(|v| v.id == item.id) {
+        metadata.insert("embedding".to_string(), serde_json::json!(vector.full_vector()));
+    }
+
+    Ok(AdamSection {
+        id: item.id.clone(),
+        section_type,
+        level: item.metadata.get(LEVEL_KEY).and_then(|level| level.parse().ok()),
+        title: item.metadata.get(TITLE_KEY).cloned(),
+        content,
+        metadata,
+        relationships: AdamRelationships::default(),
+        extra,
+    })
+}
+
+fn add_relationships(doc: &MadDocument, sections: &mut [AdamSection]) {
+    let position: HashMap<String, usize> = sections.iter()
+        .enumerate()
+        .map(|(i, section)| (section.id.clone(), i))
+        .collect();
+
+    for edge in doc.graph().edges() {
+        let (Some(&from), Some(&to)) = (position.get(&edge.from), position.get(&edge.to)) else {
+            continue;
+        };
+
+        match edge.relationship.as_str() {
+            HAS_CHILD => {
+                sections[from].relationships.children.push(edge.to.clone());
+                sections[to].relationships.parent = Some(edge.from.clone());
+            }
+            DEPENDS_ON => sections[from].relationships.dependencies.push(edge.to.clone()),
+            NEXT => {
+                sections[from].relationships.next_section = Some(edge.to.clone());
+                sections[to].relationships.previous_section = Some(edge.from.clone());
+            }
+            RELATED_TO => sections[from].relationships.related.push(edge.to.clone()),
+            SIMILAR_TO => {
+                // Older documents stored the score as a string
+                let score = edge.properties.get("score")
+                    .and_then(|score| score.as_f64().or_else(|| score.as_str()?.parse().ok()))
+                    .unwrap_or(0.0)
+                    .clamp(0.0, 1.0);
+                sections[from].relationships.semantic_similarity.push(AdamSimilarity {
+                    section_id: edge.to.clone(),
+                    similarity_score: score,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Edges come out of a hash map; restore document order
+    for section in sections.iter_mut() {
+        let rels = &mut section.relationships;
+        rels.children.sort_by_key(|id| position[id]);
+        rels.dependencies.sort_by_key(|id| position[id]);
+        rels.related.sort_by_key(|id| position[id]);
+        rels.semantic_similarity.sort_by_key(|s| position[&s.section_id]);
+    }
+}
+
+fn document_authors(meta: &DocumentMetadata) -> Vec<&str> {
+    if !meta.authors.is_empty() {
+        meta.authors.iter().map(String::as_str).collect()
+    } else if meta.author.trim().is_empty() {
+        Vec::new()
+    } else {
+        vec![meta.author.as_str()]
+    }
+}
+
+// "data:image/png;base64,...." -> ("image/png", "....")
+fn decode_data_uri(uri: &str) -> Option<(String, String)> {
+    let (header, data) = uri.strip_prefix("data:")?.split_once(',')?;
+    let mime = header.strip_suffix(";base64")?;
+    BASE64.decode(data).ok()?;
+    Some((mime.to_string(), data.to_string()))
+}
+
+fn image_text(section: &AdamSection) -> String {
+    let image = section.extra.get("image");
+    ["alt_text", "caption", "description", "title"].iter()
+        .find_map(|key| image.and_then(|image| image.get(*key)).and_then(Value::as_str))
+        .or(section.content.as_str())
+        .or(section.title.as_deref())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn collect_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) => out.push(s.clone()),
+        Value::Array(values) => values.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
+
+// Document times are unsigned milliseconds, so dates before 1970 are rejected
+fn parse_timestamp(timestamp: &str) -> MadResult<u64> {
+    let millis = DateTime::parse_from_rfc3339(timestamp)
+        .map_err(|e| MadError::InvalidInput(format!("timestamp '{}': {}", timestamp, e)))?
+        .timestamp_millis();
+    u64::try_from(millis)
+        .map_err(|_| MadError::InvalidInput(format!("timestamp '{}' is before 1970", timestamp)))
+}
+
+fn format_timestamp(millis: u64) -> MadResult<String> {
+    DateTime::from_timestamp_millis(millis as i64)
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .ok_or_else(|| MadError::InvalidInput(format!("timestamp {} out of range", millis)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn adam(authors: Value, embedding: Value) -> String {
+        json!({
+            "format": ADAM_FORMAT,
+            "version": ADAM_VERSION,
+            "metadata": {
+                "title": "Guide",
+                "created": "2024-01-01T00:00:00Z",
+                "modified": "2024-03-05T12:30:00.250Z",
+                "authors": authors
+            },
+            "sections": [
+                {"id": "s1", "type": "heading", "content": "Intro", "level": 1,
+                 "relationships": {"children": ["s2"], "next_section": "s2"}},
+                {"id": "s2", "type": "paragraph", "content": "Body text.",
+                 "metadata": {"embedding": embedding},
+                 "relationships": {"parent": "s1", "previous_section": "s1"}}
+            ],
+            "relationships": {}
+        }).to_string()
+    }
+
+    #[test]
+    fn round_trips_sections_relationships_and_embeddings() {
+        let doc = import(&adam(json!([{"name": "Ada"}]), json!([0.5, 1.0]))).unwrap();
+        assert_eq!(doc.content_items().len(), 2);
+        assert_eq!(doc.vectors()[0].embedding, vec![0.5, 1.0]);
+        // Each link is listed from both ends but stored once
+        assert_eq!(doc.graph().edge_count(), 2);
+
+        let exported: Value = serde_json::from_str(&export(&doc).unwrap()).unwrap();
+        let sections = exported["sections"].as_array().unwrap();
+        assert_eq!(sections[0]["relationships"]["children"], json!(["s2"]));
+        assert_eq!(sections[1]["relationships"]["previous_section"], "s1");
+        assert_eq!(sections[1]["metadata"]["embedding"], json!([0.5, 1.0]));
+        assert_eq!(exported["metadata"]["created"], "2024-01-01T00:00:00.000Z");
+        assert_eq!(exported["metadata"]["modified"], "2024-03-05T12:30:00.250Z");
+        // Edge IDs aren't part of the format, so compare everything else
+        let again = import(&exported.to_string()).unwrap();
+        assert_eq!(json!(again.content_items()), json!(doc.content_items()));
+        assert_eq!(json!(again.vectors()), json!(doc.vectors()));
+        assert_eq!(again.metadata().id, doc.metadata().id);
+        assert_eq!(again.metadata().modified, doc.metadata().modified);
+        assert_eq!(again.graph().edge_count(), doc.graph().edge_count());
+    }
+
+    #[test]
+    fn keeps_author_names_with_commas() {
+        for authors in [json!([{"name": "Lovelace, Ada"}]), json!([{"name": "Lovelace, Ada"}, {"name": "Babbage, C."}])] {
+            let doc = import(&adam(authors.clone(), json!([1.0]))).unwrap();
+            let exported: Value = serde_json::from_str(&export(&doc).unwrap()).unwrap();
+            assert_eq!(exported["metadata"]["authors"], authors);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        let invalid = import(&adam(json!("Ada"), json!([1.0])));
+        assert!(matches!(invalid, Err(MadError::InvalidInput(_))));
+        let bad_embedding = import(&adam(json!([{"name": "Ada"}]), json!([1.0, "two"])));
+        assert!(matches!(bad_embedding, Err(MadError::InvalidInput(_))));
+        let before_1970 = adam(json!([{"name": "Ada"}]), json!([1.0])).replace("2024-01-01", "1969-12-31");
+        assert!(matches!(import(&before_1970), Err(MadError::InvalidInput(_))));
+    }
+}
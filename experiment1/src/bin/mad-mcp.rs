@@ -0,0 +1,95 @@
+// Serve a .mad document over MCP: newline-delimited JSON-RPC 2.0 on stdin/stdout.
+//
+//   mad-mcp path/to/document.mad
+//
+// Diagnostics go to stderr so stdout carries protocol messages only.
+//
+// Text queries to mad_vector_search are embedded offline for documents built
+// with the hashed encoder. Built with the http-embeddings feature, setting
+// MAD_EMBEDDING_ENDPOINT (and optionally MAD_EMBEDDING_API_KEY) embeds them
+// with the document's recorded model instead.
+//
+// MAD_MAX_RESULTS, MAD_MAX_GRAPH_ITEMS and MAD_LIST_PAGE_SIZE override the
+// server's page size limits.
+use mad_runtime::{MadDocument, McpLimits, McpServer};
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: mad-mcp <document.mad>");
+            return ExitCode::from(2);
+        }
+    };
+
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("mad-mcp: cannot read {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let document = match MadDocument::from_bytes(&bytes) {
+        Ok(document) => document,
+        Err(e) => {
+            eprintln!("mad-mcp: cannot load {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    eprintln!("mad-mcp: serving '{}' from {}", document.metadata().title, path);
+
+    let mut server = McpServer::new();
+    let defaults = McpLimits::default();
+    let limit = |name: &str, default: usize| {
+        std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    server.set_limits(McpLimits {
+        max_results: limit("MAD_MAX_RESULTS", defaults.max_results),
+        max_graph_items: limit("MAD_MAX_GRAPH_ITEMS", defaults.max_graph_items),
+        list_page_size: limit("MAD_LIST_PAGE_SIZE", defaults.list_page_size),
+    });
+    #[cfg(feature = "http-embeddings")]
+    if let (Ok(endpoint), Some(model), Some(dimension)) = (
+        std::env::var("MAD_EMBEDDING_ENDPOINT"),
+        document.metadata().embedding_model.clone(),
+        document.metadata().embedding_dimension,
+    ) {
+        let mut provider = mad_runtime::HttpEmbeddingProvider::new(&endpoint, &model, dimension);
+        if let Ok(api_key) = std::env::var("MAD_EMBEDDING_API_KEY") {
+            provider = provider.with_api_key(&api_key);
+        }
+        eprintln!("mad-mcp: embedding queries with {} via {}", model, endpoint);
+        server.set_embedding_provider(Box::new(provider));
+    }
+    server.set_document(document);
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("mad-mcp: stdin error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = server.handle_message(&line) {
+            if writeln!(stdout, "{}", response).and_then(|_| stdout.flush()).is_err() {
+                // Client went away
+                break;
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -1,19 +1,32 @@
-use wasm_bindgen::prelude::*;
-use crate::{MadDocument, DocumentMetadata};
-use std::collections::HashMap;
+use crate::{HnswParams, MadDocument, MadError, MadResult, Quantization, SharedClock};
+use crate::chunker::{self, ChunkOptions};
+use crate::embedding::EmbeddingProvider;
+use crate::graph_db::parse_property_values;
+use crate::mcp_server::McpServer;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use std::collections::{HashMap, HashSet};
+
+// Texts sent to the embedding provider per call
+const EMBEDDING_BATCH_SIZE: usize = 32;
 
-#[wasm_bindgen]
 pub struct MadBuilder {
     document: MadDocument,
     content_items: Vec<ContentItem>,
     embeddings: Vec<EmbeddingInfo>,
     entities: Vec<EntityInfo>,
+    relationships: Vec<RelationshipInfo>,
+    index_params: HnswParams,
+    // Quantization applied at build time, and whether full precision is kept
+    quantization: Option<(Quantization, bool)>,
+    embedding_provider: Option<Box<dyn EmbeddingProvider>>,
+    clock: SharedClock,
 }
 
 #[derive(Clone)]
 struct EmbeddingInfo {
     content_id: String,
     embedding: Vec<f32>,
+    metadata: HashMap<String, String>,
 }
 
 #[derive(Clone)]
@@ -23,6 +36,15 @@ struct EntityInfo {
     properties_json: String,
 }
 
+#[derive(Clone)]
+struct RelationshipInfo {
+    id: String,
+    from: String,
+    to: String,
+    relationship: String,
+    properties_json: String,
+}
+
 #[derive(Clone)]
 struct ContentItem {
     id: String,
@@ -32,20 +54,26 @@ struct ContentItem {
     metadata: HashMap<String, String>,
 }
 
-#[wasm_bindgen]
 impl MadBuilder {
-    #[wasm_bindgen(constructor)]
     pub fn new(title: &str, author: &str) -> MadBuilder {
+        MadBuilder::with_clock(title, author, crate::clock::system_clock())
+    }
+
+    pub fn with_clock(title: &str, author: &str, clock: SharedClock) -> MadBuilder {
         MadBuilder {
-            document: MadDocument::new(title, author),
+            document: MadDocument::with_clock(title, author, clock.as_ref()),
             content_items: Vec::new(),
             embeddings: Vec::new(),
             entities: Vec::new(),
+            relationships: Vec::new(),
+            index_params: HnswParams::default(),
+            quantization: None,
+            embedding_provider: None,
+            clock,
         }
     }
 
-    #[wasm_bindgen]
-    pub fn add_text_content(&mut self, text: &str, content_type: &str) -> Result<String, JsValue> {
+    pub fn add_text_content(&mut self, text: &str, content_type: &str) -> MadResult<String> {
         let id = uuid::Uuid::new_v4().to_string();
         let content_item = ContentItem {
             id: id.clone(),
@@ -59,8 +87,7 @@ impl MadBuilder {
         Ok(id)
     }
 
-    #[wasm_bindgen]
-    pub fn add_binary_content(&mut self, data: &[u8], content_type: &str, description: &str) -> Result<String, JsValue> {
+    pub fn add_binary_content(&mut self, data: &[u8], content_type: &str, description: &str) -> MadResult<String> {
         let id = uuid::Uuid::new_v4().to_string();
         let content_item = ContentItem {
             id: id.clone(),
@@ -74,8 +101,7 @@ impl MadBuilder {
         Ok(id)
     }
 
-    #[wasm_bindgen]
-    pub fn add_html_content(&mut self, html: &str) -> Result<String, JsValue> {
+    pub fn add_html_content(&mut self, html: &str) -> MadResult<String> {
         let text_content = self.extract_text_from_html(html);
         let id = uuid::Uuid::new_v4().to_string();
         let content_item = ContentItem {
@@ -90,8 +116,7 @@ impl MadBuilder {
         Ok(id)
     }
 
-    #[wasm_bindgen]
-    pub fn add_markdown_content(&mut self, markdown: &str) -> Result<String, JsValue> {
+    pub fn add_markdown_content(&mut self, markdown: &str) -> MadResult<String> {
         let id = uuid::Uuid::new_v4().to_string();
         let content_item = ContentItem {
             id: id.clone(),
@@ -105,24 +130,141 @@ impl MadBuilder {
         Ok(id)
     }
 
-    #[wasm_bindgen]
-    pub fn add_vector_embedding(&mut self, content_id: &str, embedding: &[f32]) -> Result<(), JsValue> {
+    // Split a content item's text into child items, one per chunk, linked to the
+    // parent by a HAS_CHUNK edge and by parent_id/char_start/char_end metadata.
+    // Returns the chunk IDs in document order.
+    pub fn chunk_content(&mut self, content_id: &str, options: &ChunkOptions) -> MadResult<Vec<String>> {
+        let parent = self.content_items.iter()
+            .find(|item| item.id == content_id)
+            .ok_or_else(|| MadError::NotFound(format!("Content item {}", content_id)))?
+            .clone();
+        if parent.metadata.contains_key(chunker::PARENT_ID_KEY) {
+            return Err(MadError::InvalidInput(format!("Content item {} is already a chunk", content_id)));
+        }
+
+        // Markdown chunks are still markdown; anything else is chunked from its plain text
+        let content_type = match parent.content_type.as_str() {
+            "text/markdown" => "text/markdown",
+            _ => "text/plain",
+        };
+
+        let mut ids = Vec::new();
+        for chunk in chunker::chunk_text(&parent.text_content, options) {
+            let mut metadata = parent.metadata.clone();
+            metadata.insert(chunker::PARENT_ID_KEY.to_string(), parent.id.clone());
+            metadata.insert(chunker::CHUNK_INDEX_KEY.to_string(), chunk.index.to_string());
+            metadata.insert(chunker::CHAR_START_KEY.to_string(), chunk.start.to_string());
+            metadata.insert(chunker::CHAR_END_KEY.to_string(), chunk.end.to_string());
+            if let Some(heading) = &chunk.heading {
+                metadata.insert(chunker::HEADING_KEY.to_string(), heading.clone());
+            }
+
+            let id = uuid::Uuid::new_v4().to_string();
+            self.content_items.push(ContentItem {
+                id: id.clone(),
+                content_type: content_type.to_string(),
+                data: chunk.text.as_bytes().to_vec(),
+                text_content: chunk.text,
+                metadata,
+            });
+
+            let properties = serde_json::json!({ "chunk_index": chunk.index });
+            self.create_relationship(&parent.id, &id, chunker::CHUNK_RELATIONSHIP, &properties.to_string())?;
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    // The provider's model and dimension are recorded in the document metadata
+    pub fn set_embedding_provider(&mut self, provider: Box<dyn EmbeddingProvider>) -> MadResult<()> {
+        self.set_embedding_model(provider.model(), provider.dimension())?;
+        self.embedding_provider = Some(provider);
+        Ok(())
+    }
+
+    // Record the model behind embeddings added by hand; embeddings already
+    // added must have the given dimension
+    pub fn set_embedding_model(&mut self, model: &str, dimension: usize) -> MadResult<()> {
+        if let Some(existing) = self.embeddings.iter().find(|e| e.embedding.len() != dimension) {
+            return Err(MadError::DimensionMismatch { expected: dimension, actual: existing.embedding.len() });
+        }
+        self.document.metadata.embedding_model = Some(model.to_string());
+        self.document.metadata.embedding_dimension = Some(dimension);
+        Ok(())
+    }
+
+    // Embed every chunk, and every item that hasn't been chunked, that has no
+    // embedding yet. Returns the number of embeddings added.
+    pub fn embed_content(&mut self) -> MadResult<usize> {
+        let provider = self.embedding_provider.as_ref()
+            .ok_or_else(|| MadError::InvalidInput("No embedding provider set".to_string()))?;
+
+        let chunked: HashSet<&str> = self.content_items.iter()
+            .filter_map(|item| item.metadata.get(chunker::PARENT_ID_KEY))
+            .map(String::as_str)
+            .collect();
+        let embedded: HashSet<&str> = self.embeddings.iter().map(|e| e.content_id.as_str()).collect();
+        let pending: Vec<(String, String)> = self.content_items.iter()
+            .filter(|item| !chunked.contains(item.id.as_str()) && !embedded.contains(item.id.as_str()))
+            .filter(|item| !item.text_content.trim().is_empty())
+            .map(|item| (item.id.clone(), item.text_content.clone()))
+            .collect();
+
+        for batch in pending.chunks(EMBEDDING_BATCH_SIZE) {
+            let texts: Vec<&str> = batch.iter().map(|(_, text)| text.as_str()).collect();
+            let vectors = provider.embed(&texts)?;
+            if vectors.len() != batch.len() {
+                return Err(MadError::Provider(format!(
+                    "asked for {} embeddings, got {}",
+                    batch.len(),
+                    vectors.len()
+                )));
+            }
+
+            for ((content_id, _), embedding) in batch.iter().zip(vectors) {
+                check_dimension(&mut self.document.metadata.embedding_dimension, embedding.len())?;
+                self.embeddings.push(EmbeddingInfo {
+                    content_id: content_id.clone(),
+                    embedding,
+                    metadata: HashMap::new(),
+                });
+            }
+        }
+        Ok(pending.len())
+    }
+
+    pub fn add_vector_embedding(&mut self, content_id: &str, embedding: &[f32]) -> MadResult<()> {
+        self.add_vector_embedding_with_metadata(content_id, embedding, HashMap::new())
+    }
+
+    // Metadata (page, section, language, ...) is stored on the embedding for filtered search
+    pub fn add_vector_embedding_with_metadata(&mut self, content_id: &str, embedding: &[f32], metadata: HashMap<String, String>) -> MadResult<()> {
         // Find the content item to validate it exists
         let _content_item = self.content_items.iter()
             .find(|item| item.id == content_id)
-            .ok_or_else(|| JsValue::from_str("Content item not found"))?;
+            .ok_or_else(|| MadError::NotFound(format!("Content item {}", content_id)))?;
+        if embedding.is_empty() {
+            return Err(MadError::InvalidInput(format!("Empty embedding for {}", content_id)));
+        }
+        check_dimension(&mut self.document.metadata.embedding_dimension, embedding.len())?;
 
         // Store embedding info in builder
         let embedding_info = EmbeddingInfo {
             content_id: content_id.to_string(),
             embedding: embedding.to_vec(),
+            metadata,
         };
         self.embeddings.push(embedding_info);
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn create_entity(&mut self, id: &str, label: &str, properties_json: &str) -> Result<(), JsValue> {
+    pub fn create_entity(&mut self, id: &str, label: &str, properties_json: &str) -> MadResult<()> {
+        // Fail here rather than at build time so callers see which entity was bad
+        parse_property_values(properties_json)?;
+        if self.has_node(id) {
+            return Err(MadError::AlreadyExists(format!("Node {}", id)));
+        }
+
         // Store entity info in builder
         let entity_info = EntityInfo {
             id: id.to_string(),
@@ -133,22 +275,34 @@ impl MadBuilder {
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn create_relationship(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> Result<String, JsValue> {
-        self.document.add_graph_edge(from, to, relationship, properties_json)
+    pub fn create_relationship(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> MadResult<String> {
+        // Content items become graph nodes at build time, so they are valid endpoints too
+        if !self.has_node(from) || !self.has_node(to) {
+            return Err(MadError::NotFound("Source or target node".to_string()));
+        }
+        parse_property_values(properties_json)?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        self.relationships.push(RelationshipInfo {
+            id: id.clone(),
+            from: from.to_string(),
+            to: to.to_string(),
+            relationship: relationship.to_string(),
+            properties_json: properties_json.to_string(),
+        });
+        Ok(id)
     }
 
-    #[wasm_bindgen]
-    pub fn auto_extract_entities(&mut self, content_id: &str, entity_types: &str) -> Result<String, JsValue> {
+    pub fn auto_extract_entities(&mut self, content_id: &str, entity_types: &str) -> MadResult<String> {
         let content_item = self.content_items.iter()
             .find(|item| item.id == content_id)
-            .ok_or_else(|| JsValue::from_str("Content item not found"))?;
+            .ok_or_else(|| MadError::NotFound(format!("Content item {}", content_id)))?;
 
-        let entity_type_list: Vec<String> = serde_json::from_str(entity_types)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let entity_type_list: Vec<String> = serde_json::from_str(entity_types)?;
 
         // Simple entity extraction (in a real implementation, this would use NLP)
         let mut extracted_entities = Vec::new();
+        let mut new_entities = Vec::new();
         
         for entity_type in entity_type_list {
             match entity_type.as_str() {
@@ -161,7 +315,11 @@ impl MadBuilder {
                             "type": "PERSON",
                             "source_content": content_id
                         });
-                        self.document.add_graph_node(&entity_id, "Person", &properties.to_string())?;
+                        new_entities.push(EntityInfo {
+                            id: entity_id.clone(),
+                            label: "Person".to_string(),
+                            properties_json: properties.to_string(),
+                        });
                         extracted_entities.push(entity_id);
                     }
                 },
@@ -174,7 +332,11 @@ impl MadBuilder {
                             "type": "ORGANIZATION",
                             "source_content": content_id
                         });
-                        self.document.add_graph_node(&entity_id, "Organization", &properties.to_string())?;
+                        new_entities.push(EntityInfo {
+                            id: entity_id.clone(),
+                            label: "Organization".to_string(),
+                            properties_json: properties.to_string(),
+                        });
                         extracted_entities.push(entity_id);
                     }
                 },
@@ -182,85 +344,51 @@ impl MadBuilder {
             }
         }
 
-        serde_json::to_string(&extracted_entities).map_err(|e| JsValue::from_str(&e.to_string()))
+        self.entities.extend(new_entities);
+        Ok(serde_json::to_string(&extracted_entities)?)
     }
 
-    #[wasm_bindgen]
-    pub fn add_citation(&mut self, from_content: &str, to_content: &str, citation_type: &str) -> Result<String, JsValue> {
+    pub fn add_citation(&mut self, from_content: &str, to_content: &str, citation_type: &str) -> MadResult<String> {
         let properties = serde_json::json!({
             "citation_type": citation_type,
-            "created_at": js_sys::Date::now()
+            "created_at": self.clock.now_millis()
         });
         
-        self.document.add_graph_edge(from_content, to_content, "CITES", &properties.to_string())
+        self.create_relationship(from_content, to_content, "CITES", &properties.to_string())
     }
 
-    #[wasm_bindgen]
-    pub fn build(&mut self) -> Result<(), JsValue> {
-        // Initialize the document databases
-        self.document.init_databases()?;
-
-        // Add all content items to the document
-        for item in &self.content_items {
-            self.document.add_content(&item.content_type, &item.data[..], &item.text_content)?;
-        }
-
-        // Calculate final content hash
-        self.document.calculate_content_hash();
-
+    pub fn build(&mut self) -> MadResult<()> {
+        self.document = self.assemble_document()?;
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn create_fresh_document(&self) -> MadDocument {
-        // Create a completely fresh document and rebuild it
-        let mut new_doc = MadDocument::new(&self.document.metadata.title, &self.document.metadata.author);
-        
-        // Initialize databases
-        let _ = new_doc.init_databases();
-        
-        // Re-add all content
-        for item in &self.content_items {
-            let _ = new_doc.add_content(&item.content_type, &item.data[..], &item.text_content);
-        }
-        
-        // Note: Vectors and graph data need to be added separately via JavaScript
-        // since they're stored in the builder, not the internal document
-        
-        // Calculate hash
-        new_doc.calculate_content_hash();
-        
-        new_doc
+    pub fn create_fresh_document(&self) -> MadResult<MadDocument> {
+        self.assemble_document()
     }
 
-    #[wasm_bindgen]
     pub fn get_document(self) -> MadDocument {
         self.document
     }
 
-    #[wasm_bindgen]
     pub fn export_manifest(&self) -> String {
         let metadata = self.document.get_metadata();
+        let tools: Vec<String> = McpServer::new().tools().iter().map(|tool| tool.name.clone()).collect();
         let manifest = serde_json::json!({
             "format_version": "1.0",
             "document_metadata": serde_json::from_str::<serde_json::Value>(&metadata).unwrap_or_default(),
             "content_items": self.content_items.len(),
-            "has_vectors": !self.content_items.is_empty(),
+            "vectors": self.embeddings.len(),
+            "entities": self.entities.len(),
+            "relationships": self.relationships.len(),
+            "has_vectors": !self.embeddings.is_empty(),
             "has_graph": true,
-            "created_at": js_sys::Date::now(),
-            "tools": [
-                "mad_search",
-                "mad_vector_search", 
-                "mad_graph_query",
-                "mad_export",
-                "mad_metadata"
-            ]
+            "created_at": self.clock.now_millis(),
+            "tools": tools
         });
 
         serde_json::to_string(&manifest).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
     pub fn get_content_items_json(&self) -> String {
         // Export content items as JSON for manual reconstruction
         let items: Vec<serde_json::Value> = self.content_items.iter().map(|item| {
@@ -268,27 +396,27 @@ impl MadBuilder {
                 "id": item.id,
                 "content_type": item.content_type,
                 "text_content": item.text_content,
-                "data_base64": base64::encode(&item.data)
+                "data_base64": BASE64.encode(&item.data),
+                "metadata": item.metadata
             })
         }).collect();
         
         serde_json::to_string(&items).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
     pub fn get_embeddings_json(&self) -> String {
         // Export embeddings as JSON
         let embeddings: Vec<serde_json::Value> = self.embeddings.iter().map(|emb| {
             serde_json::json!({
                 "content_id": emb.content_id,
-                "embedding": emb.embedding
+                "embedding": emb.embedding,
+                "metadata": emb.metadata
             })
         }).collect();
         
         serde_json::to_string(&embeddings).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
     pub fn get_entities_json(&self) -> String {
         // Export entities as JSON
         let entities: Vec<serde_json::Value> = self.entities.iter().map(|ent| {
@@ -302,12 +430,75 @@ impl MadBuilder {
         serde_json::to_string(&entities).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
-    pub fn export_package(&self) -> Result<Vec<u8>, JsValue> {
-        // In a real implementation, this would create a ZIP or TAR archive
-        // containing all the database files, WASM runtime, and manifest
-        let manifest = self.export_manifest();
-        Ok(manifest.as_bytes().to_vec())
+    pub fn export_package(&self) -> MadResult<Vec<u8>> {
+        // Serialize the assembled document as a .mad container archive
+        self.assemble_document()?.to_bytes()
+    }
+
+    fn assemble_document(&self) -> MadResult<MadDocument> {
+        // Start from a fresh document that keeps the builder's identity
+        let mut doc = MadDocument::with_clock(&self.document.metadata.title, &self.document.metadata.author, self.clock.as_ref());
+        doc.metadata = self.document.metadata.clone();
+        // Quantization comes only from the builder setting, applied to the original vectors below
+        doc.metadata.vector_quantization = None;
+        doc.metadata.keep_full_precision = None;
+        doc.init_databases()?;
+        doc.build_text_index();
+
+        // Content keeps the IDs handed out by the builder so embeddings and edges still line up
+        for item in &self.content_items {
+            doc.add_content_item(crate::ContentItem {
+                id: item.id.clone(),
+                content_type: item.content_type.clone(),
+                data: BASE64.encode(&item.data),
+                text_content: item.text_content.clone(),
+                metadata: item.metadata.clone(),
+            })?;
+
+            let properties = serde_json::json!({
+                "content_type": item.content_type,
+            });
+            doc.graph_db.add_node(&item.id, "Content", &properties.to_string())?;
+        }
+
+        for embedding in &self.embeddings {
+            let text = self.content_items.iter()
+                .find(|item| item.id == embedding.content_id)
+                .map(|item| item.text_content.as_str())
+                .unwrap_or_default();
+            doc.add_vector_embedding_with_metadata(&embedding.content_id, text, &embedding.embedding, embedding.metadata.clone())?;
+        }
+        // The index is built over the quantized vectors when quantization is on
+        if let Some((quantization, keep_full_precision)) = self.quantization {
+            doc.quantize_vectors(quantization, keep_full_precision);
+        }
+        doc.build_vector_index(self.index_params);
+
+        for entity in &self.entities {
+            doc.graph_db.add_node(&entity.id, &entity.label, &entity.properties_json)?;
+        }
+
+        for rel in &self.relationships {
+            doc.graph_db.add_edge_with_id(&rel.id, &rel.from, &rel.to, &rel.relationship, &rel.properties_json)?;
+        }
+
+        doc.calculate_content_hash();
+        Ok(doc)
+    }
+
+    pub fn set_vector_index_params(&mut self, params: HnswParams) {
+        self.index_params = params;
+    }
+
+    // Store embeddings as int8 or binary. Keeping full precision lets search
+    // rescore quantized candidates exactly; dropping it gives the smallest file.
+    pub fn set_vector_quantization(&mut self, quantization: Option<Quantization>, keep_full_precision: bool) {
+        self.quantization = quantization.map(|q| (q, keep_full_precision));
+    }
+
+    fn has_node(&self, id: &str) -> bool {
+        self.content_items.iter().any(|item| item.id == id)
+            || self.entities.iter().any(|entity| entity.id == id)
     }
 
     // Helper methods for entity extraction (simplified)
@@ -427,4 +618,13 @@ impl MadBuilder {
         orgs.dedup();
         orgs
     }
-}
\ No newline at end of file
+}
+
+// The first embedding fixes the dimension unless a model was set beforehand
+fn check_dimension(recorded: &mut Option<usize>, actual: usize) -> MadResult<()> {
+    let expected = *recorded.get_or_insert(actual);
+    if expected != actual {
+        return Err(MadError::DimensionMismatch { expected, actual });
+    }
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use crate::error::{MadError, MadResult};
+use serde::{Deserialize, Serialize};
+
+// Relationship from a content item to each of its chunks, and the metadata
+// keys that link a chunk back to its parent
+pub const CHUNK_RELATIONSHIP: &str = "HAS_CHUNK";
+pub const PARENT_ID_KEY: &str = "parent_id";
+pub const CHUNK_INDEX_KEY: &str = "chunk_index";
+pub const CHAR_START_KEY: &str = "char_start";
+pub const CHAR_END_KEY: &str = "char_end";
+pub const HEADING_KEY: &str = "heading";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    // Fixed window of `max_tokens` tokens, consecutive windows sharing `overlap`
+    Tokens,
+    // Whole sentences packed up to `max_tokens`; trailing sentences are repeated
+    // at the start of the next chunk while they fit in `overlap`
+    Sentences,
+    // Like Sentences, but chunks never cross a markdown heading and record the
+    // heading path they fall under
+    Markdown,
+}
+
+impl ChunkStrategy {
+    pub fn from_name(name: &str) -> MadResult<ChunkStrategy> {
+        match name.to_ascii_lowercase().as_str() {
+            "tokens" => Ok(ChunkStrategy::Tokens),
+            "sentences" => Ok(ChunkStrategy::Sentences),
+            "markdown" => Ok(ChunkStrategy::Markdown),
+            _ => Err(MadError::InvalidInput(format!("Unknown chunk strategy '{}'", name))),
+        }
+    }
+}
+
+// Tokens are whitespace-separated words, a cheap stand-in for model tokens
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkOptions {
+    pub strategy: ChunkStrategy,
+    pub max_tokens: usize,
+    pub overlap: usize,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        ChunkOptions {
+            strategy: ChunkStrategy::Sentences,
+            max_tokens: 200,
+            overlap: 20,
+        }
+    }
+}
+
+// `start` and `end` are character offsets into the source text, end exclusive
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub heading: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+struct Word {
+    start: usize,
+    end: usize,
+}
+
+// A run of text chunked on its own: units are word ranges (sentences, lines
+// or single words) that are kept whole where possible
+struct Section {
+    units: Vec<(usize, usize)>,
+    heading: Option<String>,
+}
+
+pub fn chunk_text(text: &str, options: &ChunkOptions) -> Vec<Chunk> {
+    let chars: Vec<char> = text.chars().collect();
+    let words = split_words(&chars);
+    let max_tokens = options.max_tokens.max(1);
+    let overlap = options.overlap.min(max_tokens - 1);
+
+    let sections = match options.strategy {
+        ChunkStrategy::Tokens => vec![Section {
+            units: (0..words.len()).map(|i| (i, i + 1)).collect(),
+            heading: None,
+        }],
+        ChunkStrategy::Sentences => vec![Section {
+            units: sentences(&chars, &words, 0, words.len()),
+            heading: None,
+        }],
+        ChunkStrategy::Markdown => markdown_sections(&chars, &words),
+    };
+
+    let mut chunks = Vec::new();
+    for section in sections {
+        for (first, last) in pack(&section.units, max_tokens, overlap) {
+            let start = words[first].start;
+            let end = words[last - 1].end;
+            chunks.push(Chunk {
+                index: chunks.len(),
+                start,
+                end,
+                text: chars[start..end].iter().collect(),
+                heading: section.heading.clone(),
+            });
+        }
+    }
+    chunks
+}
+
+// Greedily pack units (word ranges) into chunks of at most `max_tokens` words.
+// Units longer than that are cut into `max_tokens` pieces first.
+fn pack(units: &[(usize, usize)], max_tokens: usize, overlap: usize) -> Vec<(usize, usize)> {
+    let units: Vec<(usize, usize)> = units.iter()
+        .flat_map(|&(first, last)| {
+            (first..last).step_by(max_tokens).map(move |start| (start, (start + max_tokens).min(last)))
+        })
+        .collect();
+    let size = |unit: &(usize, usize)| unit.1 - unit.0;
+
+    let mut chunks = Vec::new();
+    let mut i = 0;
+    while i < units.len() {
+        let mut tokens = size(&units[i]);
+        let mut j = i + 1;
+        while j < units.len() && tokens + size(&units[j]) <= max_tokens {
+            tokens += size(&units[j]);
+            j += 1;
+        }
+        chunks.push((units[i].0, units[j - 1].1));
+        if j == units.len() {
+            break;
+        }
+
+        // Step back over trailing units that fit in the overlap, leaving room for
+        // the next unit so every chunk adds new text
+        let mut next = j;
+        let mut repeated = 0;
+        while next > i + 1
+            && repeated + size(&units[next - 1]) <= overlap
+            && repeated + size(&units[next - 1]) + size(&units[j]) <= max_tokens
+        {
+            repeated += size(&units[next - 1]);
+            next -= 1;
+        }
+        i = next;
+    }
+    chunks
+}
+
+fn split_words(chars: &[char]) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (position, c) in chars.iter().chain(std::iter::once(&' ')).enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(position),
+            (true, Some(s)) => {
+                words.push(Word { start: s, end: position });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+// Sentences within words[first..last], as word ranges. A sentence ends after a
+// word ending in . ! or ? (ignoring closing quotes and brackets), or at a blank line.
+fn sentences(chars: &[char], words: &[Word], first: usize, last: usize) -> Vec<(usize, usize)> {
+    let mut units = Vec::new();
+    let mut start = first;
+    for i in first..last {
+        let word = &chars[words[i].start..words[i].end];
+        let ends_sentence = word.iter()
+            .rev()
+            .find(|c| !matches!(c, '"' | '\'' | ')' | ']' | '”' | '’'))
+            .is_some_and(|c| matches!(c, '.' | '!' | '?'));
+        let paragraph_break = words.get(i + 1)
+            .is_some_and(|next| chars[words[i].end..next.start].iter().filter(|&&c| c == '\n').count() >= 2);
+
+        if ends_sentence || paragraph_break || i + 1 == last {
+            units.push((start, i + 1));
+            start = i + 1;
+        }
+    }
+    units
+}
+
+// Split markdown at ATX headings (outside code fences). Each section's heading
+// line is its own unit so it stays at the top of the section's first chunk.
+fn markdown_sections(chars: &[char], words: &[Word]) -> Vec<Section> {
+    // (line start, line end, heading level, heading text)
+    let mut headings: Vec<(usize, usize, usize, String)> = Vec::new();
+    let mut in_fence = false;
+    let mut line_start = 0;
+    for line_end in (0..=chars.len()).filter(|&i| i == chars.len() || chars[i] == '\n') {
+        let line: String = chars[line_start..line_end].iter().collect();
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence && line.len() - trimmed.len() < 4 {
+            let level = trimmed.chars().take_while(|&c| c == '#').count();
+            let rest = &trimmed[level..];
+            if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t'])) {
+                let title = rest.trim().trim_end_matches('#').trim().to_string();
+                headings.push((line_start, line_end, level, title));
+            }
+        }
+        line_start = line_end + 1;
+    }
+
+    let mut sections = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut boundaries: Vec<usize> = headings.iter().map(|h| h.0).collect();
+    boundaries.push(chars.len());
+
+    // Text before the first heading
+    let preamble_end = words.partition_point(|w| w.start < boundaries[0]);
+    if preamble_end > 0 {
+        sections.push(Section {
+            units: sentences(chars, words, 0, preamble_end),
+            heading: None,
+        });
+    }
+
+    for (n, (_, line_end, level, title)) in headings.iter().enumerate() {
+        path.retain(|(l, _)| l < level);
+        path.push((*level, title.clone()));
+        let heading = path.iter()
+            .map(|(_, t)| t.as_str())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join(" > ");
+
+        let first = words.partition_point(|w| w.start < boundaries[n]);
+        let body = words.partition_point(|w| w.start < *line_end);
+        let last = words.partition_point(|w| w.start < boundaries[n + 1]);
+        if first == last {
+            continue;
+        }
+
+        let mut units = Vec::new();
+        if body > first {
+            units.push((first, body));
+        }
+        units.extend(sentences(chars, words, body, last));
+        sections.push(Section {
+            units,
+            heading: Some(heading).filter(|h| !h.is_empty()),
+        });
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(strategy: ChunkStrategy, max_tokens: usize, overlap: usize) -> ChunkOptions {
+        ChunkOptions { strategy, max_tokens, overlap }
+    }
+
+    #[test]
+    fn token_windows_overlap_and_locate_their_text() {
+        let text = "one two three four five six seven";
+        let chunks = chunk_text(text, &options(ChunkStrategy::Tokens, 3, 1));
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["one two three", "three four five", "five six seven"]);
+        for chunk in &chunks {
+            let located: String = text.chars().skip(chunk.start).take(chunk.end - chunk.start).collect();
+            assert_eq!(located, chunk.text);
+        }
+    }
+
+    #[test]
+    fn sentences_stay_whole() {
+        let text = "First sentence here. Second one follows. Third ends it.";
+        let chunks = chunk_text(text, &options(ChunkStrategy::Sentences, 6, 0));
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["First sentence here. Second one follows.", "Third ends it."]);
+    }
+
+    #[test]
+    fn markdown_chunks_record_headings() {
+        let text = "# Guide\nIntro text.\n## Setup\nInstall it.";
+        let chunks = chunk_text(text, &options(ChunkStrategy::Markdown, 50, 0));
+        assert!(chunks.len() >= 2);
+        assert!(chunks.iter().any(|c| c.text.contains("Install") && c.heading.as_deref().is_some_and(|h| h.contains("Setup"))));
+        assert!(chunks.iter().all(|c| !(c.text.contains("Intro") && c.text.contains("Install"))));
+        assert!(ChunkStrategy::from_name("paragraphs").is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+// Source of wall-clock time, injectable so native callers and tests can pin timestamps
+pub trait Clock: Send + Sync {
+    // Milliseconds since the Unix epoch
+    fn now_millis(&self) -> u64;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    fn now_millis(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    fn now_millis(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+// Always reports the same instant; useful for reproducible builds
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now_millis(&self) -> u64 {
+        self.0
+    }
+}
+
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
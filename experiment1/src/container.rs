@@ -0,0 +1,209 @@
+use crate::error::{MadError, MadResult};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+
+// File layout of a .mad container (see mad-spec.md)
+pub const FORMAT_VERSION: &str = "1.0";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const CONTENT_FILE: &str = "content.json";
+pub const VECTORS_FILE: &str = "vectors.json";
+pub const GRAPH_FILE: &str = "graph.json";
+pub const VECTOR_INDEX_FILE: &str = "vector_index.json";
+pub const TEXT_INDEX_FILE: &str = "text_index.json";
+pub const CHECKSUM_FILE: &str = "checksum.sha256";
+
+// An in-memory .mad archive: a tar file holding one entry per component
+// plus a sha256sum-style checksum list covering every other entry.
+pub struct MadContainer {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl MadContainer {
+    pub fn new() -> MadContainer {
+        MadContainer {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, name: &str, data: Vec<u8>) {
+        self.entries.insert(name.to_string(), data);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entries.get(name).map(|data| data.as_slice())
+    }
+
+    pub fn require(&self, name: &str) -> MadResult<&[u8]> {
+        self.get(name)
+            .ok_or_else(|| MadError::Container(format!("Container is missing {}", name)))
+    }
+
+    pub fn to_bytes(&self, mtime_secs: u64) -> MadResult<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        // Manifest first so tools can identify the archive cheaply
+        let mut names: Vec<&String> = self.entries.keys()
+            .filter(|name| name.as_str() != CHECKSUM_FILE)
+            .collect();
+        names.sort_by_key(|name| name.as_str() != MANIFEST_FILE);
+
+        for name in &names {
+            append_entry(&mut builder, name, &self.entries[*name], mtime_secs)?;
+        }
+
+        let checksums = self.checksum_list();
+        append_entry(&mut builder, CHECKSUM_FILE, checksums.as_bytes(), mtime_secs)?;
+
+        builder.into_inner().map_err(|e| MadError::Container(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> MadResult<MadContainer> {
+        let mut archive = tar::Archive::new(bytes);
+        let mut container = MadContainer::new();
+
+        for entry in archive.entries().map_err(|e| MadError::Container(e.to_string()))? {
+            let mut entry = entry.map_err(|e| MadError::Container(e.to_string()))?;
+            let name = entry.path()
+                .map_err(|e| MadError::Container(e.to_string()))?
+                .to_string_lossy()
+                .to_string();
+
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(|e| MadError::Container(e.to_string()))?;
+            if container.entries.contains_key(&name) {
+                return Err(MadError::IntegrityFailure(format!("duplicate container entry {}", name)));
+            }
+            container.entries.insert(name, data);
+        }
+
+        container.verify()?;
+        Ok(container)
+    }
+
+    // Check every entry listed in checksum.sha256. The listed names must be
+    // exactly the other entries of the container, each listed once.
+    pub fn verify(&self) -> MadResult<()> {
+        let checksums = std::str::from_utf8(self.require(CHECKSUM_FILE)?)
+            .map_err(|e| MadError::Container(e.to_string()))?;
+
+        let mut listed = BTreeSet::new();
+        for line in checksums.lines().filter(|line| !line.trim().is_empty()) {
+            let (expected, name) = line.split_once("  ")
+                .ok_or_else(|| MadError::IntegrityFailure(format!("malformed checksum line: {}", line)))?;
+            if name == CHECKSUM_FILE {
+                return Err(MadError::IntegrityFailure(format!("{} lists itself", CHECKSUM_FILE)));
+            }
+            if !listed.insert(name) {
+                return Err(MadError::IntegrityFailure(format!("{} is listed more than once", name)));
+            }
+            let data = self.require(name)?;
+            if sha256_hex(data) != expected {
+                return Err(MadError::IntegrityFailure(format!("checksum mismatch for {}", name)));
+            }
+        }
+
+        let entries: BTreeSet<&str> = self.entries.keys()
+            .map(String::as_str)
+            .filter(|name| *name != CHECKSUM_FILE)
+            .collect();
+        // Every listed name was found above, so only unlisted entries remain
+        if listed != entries {
+            let unlisted: Vec<&str> = entries.difference(&listed).copied().collect();
+            return Err(MadError::IntegrityFailure(format!(
+                "container has entries without checksums: {}", unlisted.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn checksum_list(&self) -> String {
+        self.entries.iter()
+            .filter(|(name, _)| name.as_str() != CHECKSUM_FILE)
+            .map(|(name, data)| format!("{}  {}\n", sha256_hex(data), name))
+            .collect()
+    }
+}
+
+impl Default for MadContainer {
+    fn default() -> Self {
+        MadContainer::new()
+    }
+}
+
+fn append_entry(builder: &mut tar::Builder<Vec<u8>>, name: &str, data: &[u8], mtime_secs: u64) -> MadResult<()> {
+    let mut header = tar::Header::new_ustar();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime_secs);
+    header.set_cksum();
+
+    builder.append_data(&mut header, name, data)
+        .map_err(|e| MadError::Container(e.to_string()))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(entries: &[(&str, &str)]) -> MadContainer {
+        let mut container = MadContainer::new();
+        for (name, data) in entries {
+            container.insert(name, data.as_bytes().to_vec());
+        }
+        container
+    }
+
+    fn tar(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in entries {
+            append_entry(&mut builder, name, data.as_bytes(), 0).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn round_trip_keeps_entries_and_puts_manifest_first() {
+        let bytes = container(&[(CONTENT_FILE, "[]"), (MANIFEST_FILE, "{}")]).to_bytes(0).unwrap();
+        let mut archive = tar::Archive::new(bytes.as_slice());
+        let first = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(first.path().unwrap().to_str(), Some(MANIFEST_FILE));
+
+        let loaded = MadContainer::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.get(CONTENT_FILE), Some(&b"[]"[..]));
+        assert_eq!(loaded.get(MANIFEST_FILE), Some(&b"{}"[..]));
+    }
+
+    #[test]
+    fn rejects_modified_entries() {
+        let checksums = container(&[(MANIFEST_FILE, "{}")]).checksum_list();
+        let tampered = container(&[(MANIFEST_FILE, "{ }"), (CHECKSUM_FILE, &checksums)]);
+        assert!(matches!(tampered.verify(), Err(MadError::IntegrityFailure(_))));
+    }
+
+    #[test]
+    fn rejects_unlisted_and_missing_entries() {
+        let checksums = container(&[(MANIFEST_FILE, "{}")]).checksum_list();
+        let unlisted = container(&[(MANIFEST_FILE, "{}"), (CONTENT_FILE, "[]"), (CHECKSUM_FILE, &checksums)]);
+        assert!(matches!(unlisted.verify(), Err(MadError::IntegrityFailure(_))));
+
+        let checksums = container(&[(MANIFEST_FILE, "{}"), (CONTENT_FILE, "[]")]).checksum_list();
+        let missing = container(&[(MANIFEST_FILE, "{}"), (CHECKSUM_FILE, &checksums)]);
+        assert!(matches!(missing.verify(), Err(MadError::Container(_))));
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let line = container(&[(MANIFEST_FILE, "{}")]).checksum_list();
+        let listed_twice = container(&[(MANIFEST_FILE, "{}"), (CHECKSUM_FILE, &line.repeat(2))]);
+        assert!(matches!(listed_twice.verify(), Err(MadError::IntegrityFailure(_))));
+
+        let bytes = tar(&[(MANIFEST_FILE, "{}"), (MANIFEST_FILE, "{}"), (CHECKSUM_FILE, &line)]);
+        assert!(matches!(MadContainer::from_bytes(&bytes), Err(MadError::IntegrityFailure(_))));
+    }
+}
@@ -0,0 +1,1484 @@
+use crate::error::{MadError, MadResult};
+use crate::graph_db::{GraphDatabase, GraphEdge, GraphNode};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+// Read-only Cypher subset over a GraphDatabase, e.g.
+//   MATCH (p:Person)-[:WROTE]->(d:Document) WHERE d.year >= 2020
+//   RETURN p.name, count(d) AS docs ORDER BY docs DESC LIMIT 5
+//
+//   MATCH path = (a {name: 'Alice'})-[:KNOWS*1..3]-(b:Person)
+//   RETURN DISTINCT b.name, length(path)
+//
+// Supported:
+//   MATCH      comma-separated patterns (further MATCH clauses join the same
+//              pattern); node labels (:A or :A|B), relationship types, inline
+//              property maps, ->, <- or undirected relationships, variable-length
+//              relationships (*, *n, *min..max, *..max; unbounded stops at MAX_HOPS)
+//              and named paths. A relationship is used at most once per match.
+//   WHERE      AND, OR, NOT, = <> < <= > >=, IN [...], CONTAINS, STARTS WITH,
+//              ENDS WITH, IS [NOT] NULL
+//   RETURN     [DISTINCT] expressions with AS; id, type, labels, length, size,
+//              nodes, relationships, toLower, toUpper; aggregates count, collect,
+//              sum, avg, min and max (grouped by the other columns)
+//   ORDER BY   [ASC|DESC], then SKIP and LIMIT
+// Property values keep their JSON types: strings don't compare equal to numbers
+// and lists compare element by element. `n.id` is the node's ID unless it has
+// an "id" property.
+
+pub const MAX_HOPS: usize = 10;
+const MAX_ROWS: usize = 100_000;
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CypherResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+pub fn execute(graph: &GraphDatabase, query: &str) -> MadResult<CypherResult> {
+    let query = Parser::new(query)?.query()?;
+    query.check()?;
+
+    let mut nodes: Vec<&GraphNode> = graph.nodes().collect();
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    // Without sorting, grouping or DISTINCT the first SKIP + LIMIT matches are the answer
+    let aggregating = query.items.iter().any(|item| matches!(item.expr, Expr::Aggregate(..)));
+    let stop_after = match query.limit {
+        Some(limit) if query.order.is_empty() && !query.distinct && !aggregating => Some(query.skip.saturating_add(limit)),
+        _ => None,
+    };
+
+    let mut matcher = Matcher {
+        graph,
+        query: &query,
+        nodes,
+        row: Row::new(),
+        used: Vec::new(),
+        rows: Vec::new(),
+        stop_after,
+        steps: 0,
+    };
+    if stop_after != Some(0) {
+        matcher.match_pattern(0)?;
+    }
+    let rows = std::mem::take(&mut matcher.rows);
+    project(&query, rows, aggregating)
+}
+
+// ---- Syntax ----
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Token {
+    Ident(String),
+    Str(String),
+    // Integer literals; only these are accepted as SKIP / LIMIT and path lengths
+    Num(f64),
+    // Literals written with a fraction or exponent
+    Float(f64),
+    Sym(&'static str),
+}
+
+#[derive(Clone, Debug)]
+struct Spanned {
+    token: Token,
+    start: usize,
+    end: usize,
+}
+
+struct Query {
+    patterns: Vec<Pattern>,
+    filter: Option<Expr>,
+    distinct: bool,
+    items: Vec<ReturnItem>,
+    order: Vec<SortKey>,
+    skip: usize,
+    limit: Option<usize>,
+}
+
+struct Pattern {
+    path: Option<String>,
+    start: NodePattern,
+    steps: Vec<(RelPattern, NodePattern)>,
+}
+
+struct NodePattern {
+    var: Option<String>,
+    // Every set must contain the node's label
+    labels: Vec<Vec<String>>,
+    properties: Vec<(String, Literal)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+struct RelPattern {
+    var: Option<String>,
+    types: Vec<String>,
+    direction: Direction,
+    properties: Vec<(String, Literal)>,
+    min_hops: usize,
+    max_hops: usize,
+    // *-patterns bind their variable to a list of relationships
+    variable_length: bool,
+}
+
+struct ReturnItem {
+    expr: Expr,
+    name: String,
+}
+
+struct SortKey {
+    expr: Expr,
+    descending: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Literal {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Id,
+    Type,
+    Labels,
+    Length,
+    Size,
+    Nodes,
+    Relationships,
+    ToLower,
+    ToUpper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Aggregate {
+    Count,
+    Collect,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Literal(Literal),
+    Var(String),
+    Property(Box<Expr>, String),
+    List(Vec<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Compare(Box<Expr>, Op, Box<Expr>),
+    // true for IS NOT NULL
+    IsNull(Box<Expr>, bool),
+    Function(Function, Box<Expr>),
+    // (aggregate, DISTINCT, argument; None for count(*))
+    Aggregate(Aggregate, bool, Option<Box<Expr>>),
+}
+
+const WRITE_CLAUSES: [&str; 12] = [
+    "CREATE", "MERGE", "DELETE", "DETACH", "SET", "REMOVE", "CALL", "OPTIONAL", "WITH", "UNWIND", "FOREACH", "UNION",
+];
+
+pub(crate) struct Parser<'q> {
+    tokens: Vec<Spanned>,
+    position: usize,
+    source: &'q str,
+}
+
+// The token helpers are shared with the script importer in cypher_export
+impl<'q> Parser<'q> {
+    pub(crate) fn new(source: &'q str) -> MadResult<Parser<'q>> {
+        Ok(Parser { tokens: tokenize(source)?, position: 0, source })
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|t| &t.token)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset).map(|t| &t.token)
+    }
+
+    pub(crate) fn next(&mut self) -> MadResult<Token> {
+        let token = self.peek().cloned()
+            .ok_or_else(|| cypher_error("unexpected end of query".to_string()))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    pub(crate) fn sym(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Sym(s)) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn expect(&mut self, symbol: &str) -> MadResult<()> {
+        if self.sym(symbol) {
+            return Ok(());
+        }
+        Err(cypher_error(match self.peek() {
+            Some(token) => format!("expected '{}', found {}", symbol, describe(token)),
+            None => format!("expected '{}' at end of query", symbol),
+        }))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    pub(crate) fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: &str) -> MadResult<()> {
+        if self.keyword(keyword) {
+            return Ok(());
+        }
+        Err(cypher_error(match self.peek() {
+            Some(token) => format!("expected {}, found {}", keyword, describe(token)),
+            None => format!("expected {} at end of query", keyword),
+        }))
+    }
+
+    pub(crate) fn ident(&mut self) -> MadResult<String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            token => Err(cypher_error(format!("expected a name, found {}", describe(&token)))),
+        }
+    }
+
+    fn reject_writes(&self) -> MadResult<()> {
+        match self.peek() {
+            Some(Token::Ident(word)) if WRITE_CLAUSES.iter().any(|c| word.eq_ignore_ascii_case(c)) => {
+                Err(MadError::Unsupported(format!(
+                    "cypher: {} (only read-only MATCH ... RETURN queries are supported)",
+                    word.to_uppercase()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn query(&mut self) -> MadResult<Query> {
+        self.reject_writes()?;
+        self.expect_keyword("MATCH")?;
+
+        let mut patterns = Vec::new();
+        let mut filters = Vec::new();
+        loop {
+            patterns.push(self.pattern()?);
+            while self.sym(",") {
+                patterns.push(self.pattern()?);
+            }
+            if self.keyword("WHERE") {
+                filters.push(self.expr()?);
+            }
+            self.reject_writes()?;
+            if !self.keyword("MATCH") {
+                break;
+            }
+        }
+        let filter = match filters.len() {
+            0 => None,
+            1 => filters.pop(),
+            _ => Some(Expr::And(filters)),
+        };
+
+        self.expect_keyword("RETURN")?;
+        let distinct = self.keyword("DISTINCT");
+        let mut items = vec![self.return_item()?];
+        while self.sym(",") {
+            items.push(self.return_item()?);
+        }
+
+        let mut order = Vec::new();
+        if self.keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let expr = self.expr()?;
+                let descending = self.keyword("DESC") || self.keyword("DESCENDING");
+                if !descending && !self.keyword("ASC") {
+                    self.keyword("ASCENDING");
+                }
+                order.push(SortKey { expr, descending });
+                if !self.sym(",") {
+                    break;
+                }
+            }
+        }
+
+        let skip = if self.keyword("SKIP") { self.count()? } else { 0 };
+        let limit = if self.keyword("LIMIT") { Some(self.count()?) } else { None };
+        self.sym(";");
+        if let Some(token) = self.peek() {
+            return Err(cypher_error(format!("unexpected {}", describe(token))));
+        }
+
+        Ok(Query { patterns, filter, distinct, items, order, skip, limit })
+    }
+
+    fn return_item(&mut self) -> MadResult<ReturnItem> {
+        let start = self.tokens.get(self.position).map(|t| t.start).unwrap_or(self.source.len());
+        let expr = self.expr()?;
+        let end = self.tokens[self.position - 1].end;
+        let name = if self.keyword("AS") {
+            self.ident()?
+        } else {
+            self.source[start..end].to_string()
+        };
+        Ok(ReturnItem { expr, name })
+    }
+
+    fn count(&mut self) -> MadResult<usize> {
+        match self.next()? {
+            Token::Num(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            token => Err(cypher_error(format!("expected a non-negative integer, found {}", describe(&token)))),
+        }
+    }
+
+    fn pattern(&mut self) -> MadResult<Pattern> {
+        let path = match (self.peek(), self.peek_at(1)) {
+            (Some(Token::Ident(_)), Some(Token::Sym("="))) => {
+                let name = self.ident()?;
+                self.position += 1;
+                Some(name)
+            }
+            _ => None,
+        };
+
+        let start = self.node_pattern()?;
+        let mut steps = Vec::new();
+        while matches!(self.peek(), Some(Token::Sym("-")) | Some(Token::Sym("<"))) {
+            let rel = self.rel_pattern()?;
+            steps.push((rel, self.node_pattern()?));
+        }
+        Ok(Pattern { path, start, steps })
+    }
+
+    fn node_pattern(&mut self) -> MadResult<NodePattern> {
+        self.expect("(")?;
+        let var = match self.peek() {
+            Some(Token::Ident(_)) => Some(self.ident()?),
+            _ => None,
+        };
+
+        let mut labels = Vec::new();
+        while self.sym(":") {
+            let mut alternatives = vec![self.ident()?];
+            while self.sym("|") {
+                self.sym(":");
+                alternatives.push(self.ident()?);
+            }
+            labels.push(alternatives);
+        }
+
+        let properties = self.property_map()?;
+        self.expect(")")?;
+        Ok(NodePattern { var, labels, properties })
+    }
+
+    fn rel_pattern(&mut self) -> MadResult<RelPattern> {
+        let incoming = self.sym("<");
+        self.expect("-")?;
+
+        let mut rel = RelPattern {
+            var: None,
+            types: Vec::new(),
+            direction: Direction::Both,
+            properties: Vec::new(),
+            min_hops: 1,
+            max_hops: 1,
+            variable_length: false,
+        };
+
+        if self.sym("[") {
+            if let Some(Token::Ident(_)) = self.peek() {
+                rel.var = Some(self.ident()?);
+            }
+            if self.sym(":") {
+                rel.types.push(self.ident()?);
+                while self.sym("|") {
+                    self.sym(":");
+                    rel.types.push(self.ident()?);
+                }
+            }
+            if self.sym("*") {
+                rel.variable_length = true;
+                let min = match self.peek() {
+                    Some(Token::Num(_)) => Some(self.count()?),
+                    _ => None,
+                };
+                let max = if self.sym("..") {
+                    match self.peek() {
+                        Some(Token::Num(_)) => Some(self.count()?),
+                        _ => None,
+                    }
+                } else {
+                    // *n means exactly n hops
+                    min
+                };
+                rel.min_hops = min.unwrap_or(1);
+                rel.max_hops = max.unwrap_or(MAX_HOPS);
+                if rel.min_hops > rel.max_hops {
+                    return Err(cypher_error(format!("empty hop range *{}..{}", rel.min_hops, rel.max_hops)));
+                }
+            }
+            rel.properties = self.property_map()?;
+            self.expect("]")?;
+        }
+
+        self.expect("-")?;
+        let outgoing = self.sym(">");
+        rel.direction = match (incoming, outgoing) {
+            (true, true) => return Err(cypher_error("a relationship cannot point both ways".to_string())),
+            (true, false) => Direction::In,
+            (false, true) => Direction::Out,
+            (false, false) => Direction::Both,
+        };
+        Ok(rel)
+    }
+
+    fn property_map(&mut self) -> MadResult<Vec<(String, Literal)>> {
+        let mut properties = Vec::new();
+        if !self.sym("{") {
+            return Ok(properties);
+        }
+        if self.sym("}") {
+            return Ok(properties);
+        }
+        loop {
+            let key = self.ident()?;
+            self.expect(":")?;
+            properties.push((key, self.literal()?));
+            if !self.sym(",") {
+                break;
+            }
+        }
+        self.expect("}")?;
+        Ok(properties)
+    }
+
+    fn literal(&mut self) -> MadResult<Literal> {
+        match self.expr()? {
+            Expr::Literal(literal) => Ok(literal),
+            Expr::Neg(inner) => match *inner {
+                Expr::Literal(Literal::Number(n)) => Ok(Literal::Number(-n)),
+                _ => Err(cypher_error("property maps in patterns take literal values".to_string())),
+            },
+            _ => Err(cypher_error("property maps in patterns take literal values".to_string())),
+        }
+    }
+
+    fn expr(&mut self) -> MadResult<Expr> {
+        let mut terms = vec![self.and_expr()?];
+        while self.keyword("OR") {
+            terms.push(self.and_expr()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::Or(terms) })
+    }
+
+    fn and_expr(&mut self) -> MadResult<Expr> {
+        let mut terms = vec![self.not_expr()?];
+        while self.keyword("AND") {
+            terms.push(self.not_expr()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::And(terms) })
+    }
+
+    fn not_expr(&mut self) -> MadResult<Expr> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not_expr()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> MadResult<Expr> {
+        let left = self.unary()?;
+
+        if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull(Box::new(left), negated));
+        }
+
+        let op = match self.peek() {
+            Some(Token::Sym("=")) => Op::Eq,
+            Some(Token::Sym("<>")) | Some(Token::Sym("!=")) => Op::Ne,
+            Some(Token::Sym("<")) => Op::Lt,
+            Some(Token::Sym("<=")) => Op::Le,
+            Some(Token::Sym(">")) => Op::Gt,
+            Some(Token::Sym(">=")) => Op::Ge,
+            _ if self.is_keyword("IN") => Op::In,
+            _ if self.is_keyword("CONTAINS") => Op::Contains,
+            _ if self.is_keyword("STARTS") => Op::StartsWith,
+            _ if self.is_keyword("ENDS") => Op::EndsWith,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        if matches!(op, Op::StartsWith | Op::EndsWith) {
+            self.expect_keyword("WITH")?;
+        }
+
+        let right = self.unary()?;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn unary(&mut self) -> MadResult<Expr> {
+        if self.sym("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+
+        let mut expr = self.primary()?;
+        while self.sym(".") {
+            expr = Expr::Property(Box::new(expr), self.ident()?);
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> MadResult<Expr> {
+        match self.next()? {
+            Token::Str(s) => Ok(Expr::Literal(Literal::Str(s))),
+            Token::Num(n) | Token::Float(n) => Ok(Expr::Literal(Literal::Number(n))),
+            Token::Sym("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Sym("[") => {
+                let mut items = Vec::new();
+                if !self.sym("]") {
+                    loop {
+                        items.push(self.expr()?);
+                        if !self.sym(",") {
+                            break;
+                        }
+                    }
+                    self.expect("]")?;
+                }
+                Ok(Expr::List(items))
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::Sym("(")) => {
+                self.position += 1;
+                self.call(&name)
+            }
+            Token::Ident(name) => Ok(match name.to_ascii_lowercase().as_str() {
+                "true" => Expr::Literal(Literal::Bool(true)),
+                "false" => Expr::Literal(Literal::Bool(false)),
+                "null" => Expr::Literal(Literal::Null),
+                _ => Expr::Var(name),
+            }),
+            token => Err(cypher_error(format!("unexpected {}", describe(&token)))),
+        }
+    }
+
+    // Function call; the opening parenthesis has been consumed
+    fn call(&mut self, name: &str) -> MadResult<Expr> {
+        let aggregate = match name.to_ascii_lowercase().as_str() {
+            "count" => Some(Aggregate::Count),
+            "collect" => Some(Aggregate::Collect),
+            "sum" => Some(Aggregate::Sum),
+            "avg" => Some(Aggregate::Avg),
+            "min" => Some(Aggregate::Min),
+            "max" => Some(Aggregate::Max),
+            _ => None,
+        };
+
+        if let Some(aggregate) = aggregate {
+            if aggregate == Aggregate::Count && self.sym("*") {
+                self.expect(")")?;
+                return Ok(Expr::Aggregate(aggregate, false, None));
+            }
+            let distinct = self.keyword("DISTINCT");
+            let argument = self.expr()?;
+            self.expect(")")?;
+            return Ok(Expr::Aggregate(aggregate, distinct, Some(Box::new(argument))));
+        }
+
+        let function = match name.to_ascii_lowercase().as_str() {
+            "id" => Function::Id,
+            "type" => Function::Type,
+            "labels" => Function::Labels,
+            "length" => Function::Length,
+            "size" => Function::Size,
+            "nodes" => Function::Nodes,
+            "relationships" => Function::Relationships,
+            "tolower" => Function::ToLower,
+            "toupper" => Function::ToUpper,
+            _ => return Err(cypher_error(format!("unknown function '{}'", name))),
+        };
+        let argument = self.expr()?;
+        self.expect(")")?;
+        Ok(Expr::Function(function, Box::new(argument)))
+    }
+}
+
+impl Query {
+    // Reject unknown or conflicting variables and misplaced aggregates before matching
+    fn check(&self) -> MadResult<()> {
+        let mut nodes = HashSet::new();
+        let mut others = HashSet::new();
+        for pattern in &self.patterns {
+            let node_patterns = std::iter::once(&pattern.start).chain(pattern.steps.iter().map(|(_, node)| node));
+            nodes.extend(node_patterns.filter_map(|node| node.var.as_deref()));
+            let rel_vars = pattern.steps.iter().filter_map(|(rel, _)| rel.var.as_deref());
+            for var in rel_vars.chain(pattern.path.as_deref()) {
+                if !others.insert(var) {
+                    return Err(cypher_error(format!("variable '{}' is bound twice", var)));
+                }
+            }
+        }
+        if let Some(var) = nodes.intersection(&others).next() {
+            return Err(cypher_error(format!("'{}' is used as a node and as a relationship or path", var)));
+        }
+
+        let known: HashSet<&str> = nodes.union(&others).copied().collect();
+        if let Some(filter) = &self.filter {
+            check_expr(filter, &known, false)?;
+        }
+        for item in &self.items {
+            check_expr(&item.expr, &known, true)?;
+        }
+
+        let mut sortable = known.clone();
+        sortable.extend(self.items.iter().map(|item| item.name.as_str()));
+        for key in &self.order {
+            if !self.items.iter().any(|item| item.expr == key.expr) {
+                check_expr(&key.expr, &sortable, false)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_expr(expr: &Expr, known: &HashSet<&str>, aggregate_allowed: bool) -> MadResult<()> {
+    match expr {
+        Expr::Literal(_) => Ok(()),
+        Expr::Var(name) if known.contains(name.as_str()) => Ok(()),
+        Expr::Var(name) => Err(cypher_error(format!("unknown variable '{}'", name))),
+        Expr::Property(inner, _) | Expr::Neg(inner) | Expr::Not(inner) | Expr::IsNull(inner, _) | Expr::Function(_, inner) => {
+            check_expr(inner, known, false)
+        }
+        Expr::List(items) | Expr::And(items) | Expr::Or(items) => {
+            items.iter().try_for_each(|item| check_expr(item, known, false))
+        }
+        Expr::Compare(left, _, right) => {
+            check_expr(left, known, false)?;
+            check_expr(right, known, false)
+        }
+        Expr::Aggregate(..) if !aggregate_allowed => {
+            Err(cypher_error("aggregates are only allowed as whole RETURN columns".to_string()))
+        }
+        Expr::Aggregate(_, _, argument) => match argument {
+            Some(argument) => check_expr(argument, known, false),
+            None => Ok(()),
+        },
+    }
+}
+
+// ---- Matching ----
+
+#[derive(Clone, Debug)]
+enum Value<'g> {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    List(Vec<Value<'g>>),
+    Node(&'g GraphNode),
+    Edge(&'g GraphEdge),
+    Path(Vec<&'g GraphNode>, Vec<&'g GraphEdge>),
+}
+
+type Row<'g> = HashMap<String, Value<'g>>;
+
+struct Matcher<'g, 'q> {
+    graph: &'g GraphDatabase,
+    query: &'q Query,
+    // All nodes, sorted by ID so results come back in a stable order
+    nodes: Vec<&'g GraphNode>,
+    // Variables bound so far, and the relationships they use
+    row: Row<'g>,
+    used: Vec<&'g str>,
+    rows: Vec<Row<'g>>,
+    stop_after: Option<usize>,
+    steps: usize,
+}
+
+// Nodes and relationships of the pattern being matched
+struct Walk<'g> {
+    nodes: Vec<&'g GraphNode>,
+    edges: Vec<&'g GraphEdge>,
+}
+
+impl<'g> Matcher<'g, '_> {
+    // Each match_* returns Ok(false) once enough rows have been found
+    fn match_pattern(&mut self, index: usize) -> MadResult<bool> {
+        let query = self.query;
+        let Some(pattern) = query.patterns.get(index) else {
+            return self.emit();
+        };
+
+        let candidates = match pattern.start.var.as_ref().and_then(|var| self.row.get(var)) {
+            Some(Value::Node(node)) => vec![*node],
+            Some(_) => return Ok(true),
+            // A labelled start node only needs the nodes with one of its labels
+            None => match pattern.start.labels.first() {
+                Some(alternatives) => {
+                    let mut nodes: Vec<&'g GraphNode> = alternatives.iter()
+                        .flat_map(|label| self.graph.nodes_by_label(label))
+                        .collect();
+                    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+                    nodes.dedup_by(|a, b| a.id == b.id);
+                    nodes
+                }
+                None => self.nodes.clone(),
+            },
+        };
+
+        for node in candidates {
+            if !node_matches(&pattern.start, node) {
+                continue;
+            }
+            let bound = bind(&mut self.row, &pattern.start.var, Value::Node(node));
+            let mut walk = Walk { nodes: vec![node], edges: Vec::new() };
+            let more = self.match_step(index, 0, node, &mut walk)?;
+            unbind(&mut self.row, bound);
+            if !more {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn match_step(&mut self, index: usize, step: usize, current: &'g GraphNode, walk: &mut Walk<'g>) -> MadResult<bool> {
+        let pattern = &self.query.patterns[index];
+        if step == pattern.steps.len() {
+            let path = Value::Path(walk.nodes.clone(), walk.edges.clone());
+            let bound = bind(&mut self.row, &pattern.path, path);
+            let more = self.match_pattern(index + 1)?;
+            unbind(&mut self.row, bound);
+            return Ok(more);
+        }
+        self.expand(index, step, current, &mut Vec::new(), walk)
+    }
+
+    // Follow the step's relationship one hop at a time; `hops` are the edges
+    // taken for it so far
+    fn expand(&mut self, index: usize, step: usize, current: &'g GraphNode, hops: &mut Vec<&'g GraphEdge>, walk: &mut Walk<'g>) -> MadResult<bool> {
+        let (rel, target) = &self.query.patterns[index].steps[step];
+
+        if hops.len() >= rel.min_hops && node_matches(target, current) && bound_to(&self.row, &target.var, current) {
+            let value = if rel.variable_length {
+                Value::List(hops.iter().map(|edge| Value::Edge(edge)).collect())
+            } else {
+                Value::Edge(hops[0])
+            };
+            let rel_bound = bind(&mut self.row, &rel.var, value);
+            let node_bound = bind(&mut self.row, &target.var, Value::Node(current));
+            let more = self.match_step(index, step + 1, current, walk)?;
+            unbind(&mut self.row, node_bound);
+            unbind(&mut self.row, rel_bound);
+            if !more {
+                return Ok(false);
+            }
+        }
+
+        if hops.len() == rel.max_hops {
+            return Ok(true);
+        }
+
+        for (edge, next) in self.neighbours(current, rel) {
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return Err(cypher_error("query explores too many paths; narrow the pattern or shorten variable-length relationships".to_string()));
+            }
+            if self.used.contains(&edge.id.as_str()) || !properties_match(&rel.properties, |key| edge_property(edge, key)) {
+                continue;
+            }
+
+            self.used.push(&edge.id);
+            hops.push(edge);
+            walk.edges.push(edge);
+            walk.nodes.push(next);
+            let more = self.expand(index, step, next, hops, walk)?;
+            walk.nodes.pop();
+            walk.edges.pop();
+            hops.pop();
+            self.used.pop();
+            if !more {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn neighbours(&self, node: &GraphNode, rel: &RelPattern) -> Vec<(&'g GraphEdge, &'g GraphNode)> {
+        let mut found = Vec::new();
+        if rel.direction != Direction::In {
+            found.extend(self.graph.outgoing_edges(&node.id)
+                .filter_map(|edge| self.graph.node(&edge.to).map(|next| (edge, next))));
+        }
+        if rel.direction != Direction::Out {
+            // Undirected patterns already saw self-loops as outgoing edges
+            found.extend(self.graph.incoming_edges(&node.id)
+                .filter(|edge| rel.direction == Direction::In || edge.from != edge.to)
+                .filter_map(|edge| self.graph.node(&edge.from).map(|next| (edge, next))));
+        }
+        found.retain(|(edge, _)| rel.types.is_empty() || rel.types.contains(&edge.relationship));
+        found
+    }
+
+    fn emit(&mut self) -> MadResult<bool> {
+        if let Some(filter) = &self.query.filter {
+            if !matches!(eval(filter, &self.row)?, Value::Bool(true)) {
+                return Ok(true);
+            }
+        }
+        if self.rows.len() == MAX_ROWS {
+            return Err(cypher_error(format!("query matches more than {} rows; narrow the pattern", MAX_ROWS)));
+        }
+        self.rows.push(self.row.clone());
+        Ok(self.stop_after.is_none_or(|limit| self.rows.len() < limit))
+    }
+}
+
+fn node_matches(pattern: &NodePattern, node: &GraphNode) -> bool {
+    pattern.labels.iter().all(|alternatives| alternatives.contains(&node.label))
+        && properties_match(&pattern.properties, |key| node_property(node, key))
+}
+
+fn properties_match<'g>(expected: &[(String, Literal)], property: impl Fn(&str) -> Value<'g>) -> bool {
+    expected.iter().all(|(key, literal)| equal(&property(key), &Value::from(literal)))
+}
+
+// A variable bound by an earlier part of the pattern must be the same node
+fn bound_to(row: &Row, var: &Option<String>, node: &GraphNode) -> bool {
+    match var.as_ref().and_then(|var| row.get(var)) {
+        Some(Value::Node(bound)) => bound.id == node.id,
+        Some(_) => false,
+        None => true,
+    }
+}
+
+// Returns the variable if this call introduced it, so it can be unbound later
+fn bind<'g>(row: &mut Row<'g>, var: &Option<String>, value: Value<'g>) -> Option<String> {
+    let var = var.as_ref()?;
+    if row.contains_key(var) {
+        return None;
+    }
+    row.insert(var.clone(), value);
+    Some(var.clone())
+}
+
+fn unbind(row: &mut Row, var: Option<String>) {
+    if let Some(var) = var {
+        row.remove(&var);
+    }
+}
+
+fn node_property<'g>(node: &GraphNode, key: &str) -> Value<'g> {
+    match node.properties.get(key) {
+        Some(value) => Value::from(value),
+        None if key == "id" => Value::Str(node.id.clone()),
+        None => Value::Null,
+    }
+}
+
+fn edge_property<'g>(edge: &GraphEdge, key: &str) -> Value<'g> {
+    match edge.properties.get(key) {
+        Some(value) => Value::from(value),
+        None if key == "id" => Value::Str(edge.id.clone()),
+        None => Value::Null,
+    }
+}
+
+// ---- Evaluation ----
+
+// Stored properties never hold objects; see graph_db::check_property_values
+impl From<&serde_json::Value> for Value<'_> {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => n.as_f64().map(Value::Number).unwrap_or(Value::Null),
+            serde_json::Value::String(s) => Value::Str(s.clone()),
+            serde_json::Value::Array(items) => Value::List(items.iter().map(Value::from).collect()),
+            serde_json::Value::Null | serde_json::Value::Object(_) => Value::Null,
+        }
+    }
+}
+
+impl From<&Literal> for Value<'_> {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Null => Value::Null,
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Number(n) => Value::Number(*n),
+            Literal::Str(s) => Value::Str(s.clone()),
+        }
+    }
+}
+
+impl Value<'_> {
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::List(_) => "list",
+            Value::Node(_) => "node",
+            Value::Edge(_) => "relationship",
+            Value::Path(..) => "path",
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 9.0e15 => serde_json::Value::from(*n as i64),
+            Value::Number(n) => serde_json::Value::from(*n),
+            Value::Str(s) => serde_json::Value::String(s.clone()),
+            Value::List(items) => serde_json::Value::Array(items.iter().map(Value::to_json).collect()),
+            Value::Node(node) => serde_json::to_value(node).unwrap_or_default(),
+            Value::Edge(edge) => serde_json::to_value(edge).unwrap_or_default(),
+            Value::Path(nodes, edges) => serde_json::json!({
+                "nodes": nodes,
+                "edges": edges,
+                "length": edges.len()
+            }),
+        }
+    }
+}
+
+fn eval<'g>(expr: &Expr, row: &Row<'g>) -> MadResult<Value<'g>> {
+    Ok(match expr {
+        Expr::Literal(literal) => Value::from(literal),
+        Expr::Var(name) => row.get(name).cloned()
+            .ok_or_else(|| cypher_error(format!("unknown variable '{}'", name)))?,
+        Expr::Property(base, key) => match eval(base, row)? {
+            Value::Node(node) => node_property(node, key),
+            Value::Edge(edge) => edge_property(edge, key),
+            Value::Null => Value::Null,
+            other => return Err(cypher_error(format!("cannot read property '{}' of a {}", key, other.type_name()))),
+        },
+        Expr::List(items) => Value::List(items.iter().map(|item| eval(item, row)).collect::<MadResult<_>>()?),
+        Expr::Neg(inner) => match eval(inner, row)? {
+            Value::Null => Value::Null,
+            value => match value.as_number() {
+                Some(n) => Value::Number(-n),
+                None => return Err(cypher_error(format!("cannot negate a {}", value.type_name()))),
+            },
+        },
+        Expr::Not(inner) => match truth(&eval(inner, row)?) {
+            Some(b) => Value::Bool(!b),
+            None => Value::Null,
+        },
+        // Three-valued logic: null is "unknown"
+        Expr::And(terms) => {
+            let mut result = Some(true);
+            for term in terms {
+                match truth(&eval(term, row)?) {
+                    Some(false) => return Ok(Value::Bool(false)),
+                    None => result = None,
+                    Some(true) => {}
+                }
+            }
+            result.map(Value::Bool).unwrap_or(Value::Null)
+        }
+        Expr::Or(terms) => {
+            let mut result = Some(false);
+            for term in terms {
+                match truth(&eval(term, row)?) {
+                    Some(true) => return Ok(Value::Bool(true)),
+                    None => result = None,
+                    Some(false) => {}
+                }
+            }
+            result.map(Value::Bool).unwrap_or(Value::Null)
+        }
+        Expr::Compare(left, op, right) => compare(&eval(left, row)?, *op, &eval(right, row)?),
+        Expr::IsNull(inner, negated) => Value::Bool(matches!(eval(inner, row)?, Value::Null) != *negated),
+        Expr::Function(function, argument) => call(*function, eval(argument, row)?)?,
+        Expr::Aggregate(..) => return Err(cypher_error("aggregates are only allowed as whole RETURN columns".to_string())),
+    })
+}
+
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        _ => None,
+    }
+}
+
+fn compare<'g>(left: &Value<'g>, op: Op, right: &Value<'g>) -> Value<'g> {
+    if matches!(left, Value::Null) || matches!(right, Value::Null) {
+        return Value::Null;
+    }
+    let text = |f: fn(&str, &str) -> bool| match (left, right) {
+        (Value::Str(a), Value::Str(b)) => Value::Bool(f(a, b)),
+        _ => Value::Null,
+    };
+    let ordered = |f: fn(Ordering) -> bool| order(left, right).map(|o| Value::Bool(f(o))).unwrap_or(Value::Null);
+
+    match op {
+        Op::Eq => Value::Bool(equal(left, right)),
+        Op::Ne => Value::Bool(!equal(left, right)),
+        Op::Lt => ordered(|o| o == Ordering::Less),
+        Op::Le => ordered(|o| o != Ordering::Greater),
+        Op::Gt => ordered(|o| o == Ordering::Greater),
+        Op::Ge => ordered(|o| o != Ordering::Less),
+        Op::In => match right {
+            Value::List(items) => Value::Bool(items.iter().any(|item| equal(left, item))),
+            _ => Value::Null,
+        },
+        Op::Contains => text(|a, b| a.contains(b)),
+        Op::StartsWith => text(|a, b| a.starts_with(b)),
+        Op::EndsWith => text(|a, b| a.ends_with(b)),
+    }
+}
+
+fn equal(left: &Value, right: &Value) -> bool {
+    if let (Some(a), Some(b)) = (left.as_number(), right.as_number()) {
+        return a == b;
+    }
+    match (left, right) {
+        (Value::Str(a), Value::Str(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Node(a), Value::Node(b)) => a.id == b.id,
+        (Value::Edge(a), Value::Edge(b)) => a.id == b.id,
+        (Value::List(a), Value::List(b)) => a.len() == b.len() && a.iter().zip(b).all(|(x, y)| equal(x, y)),
+        (Value::Path(_, a), Value::Path(_, b)) => a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.id == y.id),
+        _ => false,
+    }
+}
+
+// None when the values can't be ordered against each other
+fn order(left: &Value, right: &Value) -> Option<Ordering> {
+    if let (Some(a), Some(b)) = (left.as_number(), right.as_number()) {
+        return a.partial_cmp(&b);
+    }
+    match (left, right) {
+        (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+// Total order for ORDER BY and min/max: numbers, strings, booleans, other
+// values, then nulls
+fn sort_order(left: &Value, right: &Value) -> Ordering {
+    let rank = |value: &Value| match value {
+        _ if value.as_number().is_some() => 0,
+        Value::Str(_) => 1,
+        Value::Bool(_) => 2,
+        Value::Null => 4,
+        _ => 3,
+    };
+    rank(left).cmp(&rank(right))
+        .then_with(|| order(left, right).unwrap_or(Ordering::Equal))
+        .then_with(|| left.to_json().to_string().cmp(&right.to_json().to_string()))
+}
+
+fn call(function: Function, argument: Value) -> MadResult<Value> {
+    if matches!(argument, Value::Null) {
+        return Ok(Value::Null);
+    }
+    let result = match (function, &argument) {
+        (Function::Id, Value::Node(node)) => Some(Value::Str(node.id.clone())),
+        (Function::Id, Value::Edge(edge)) => Some(Value::Str(edge.id.clone())),
+        (Function::Type, Value::Edge(edge)) => Some(Value::Str(edge.relationship.clone())),
+        (Function::Labels, Value::Node(node)) => Some(Value::List(vec![Value::Str(node.label.clone())])),
+        (Function::Length, Value::Path(_, edges)) => Some(Value::Number(edges.len() as f64)),
+        (Function::Length | Function::Size, Value::List(items)) => Some(Value::Number(items.len() as f64)),
+        (Function::Length | Function::Size, Value::Str(s)) => Some(Value::Number(s.chars().count() as f64)),
+        (Function::Nodes, Value::Path(nodes, _)) => Some(Value::List(nodes.iter().map(|node| Value::Node(node)).collect())),
+        (Function::Relationships, Value::Path(_, edges)) => Some(Value::List(edges.iter().map(|edge| Value::Edge(edge)).collect())),
+        (Function::ToLower, Value::Str(s)) => Some(Value::Str(s.to_lowercase())),
+        (Function::ToUpper, Value::Str(s)) => Some(Value::Str(s.to_uppercase())),
+        _ => None,
+    };
+    result.ok_or_else(|| cypher_error(format!("{:?}() does not accept a {}", function, argument.type_name()).to_lowercase()))
+}
+
+// ---- Projection ----
+
+fn project(query: &Query, rows: Vec<Row>, aggregating: bool) -> MadResult<CypherResult> {
+    // Projected values, plus the matched row for ORDER BY keys that aren't returned
+    let mut projected: Vec<(Vec<Value>, Option<&Row>)> = Vec::new();
+
+    if aggregating {
+        // Group by the non-aggregate columns, keeping groups in first-seen order
+        let mut groups: Vec<(Vec<Value>, Vec<Vec<Value>>)> = Vec::new();
+        let mut group_index: HashMap<String, usize> = HashMap::new();
+        for row in &rows {
+            let mut key = Vec::new();
+            let mut inputs = Vec::new();
+            for item in &query.items {
+                match &item.expr {
+                    Expr::Aggregate(_, _, Some(argument)) => inputs.push(eval(argument, row)?),
+                    Expr::Aggregate(_, _, None) => inputs.push(Value::Bool(true)),
+                    expr => key.push(eval(expr, row)?),
+                }
+            }
+            let key_text = json_key(&key);
+            let index = *group_index.entry(key_text).or_insert_with(|| {
+                groups.push((key, vec![Vec::new(); inputs.len()]));
+                groups.len() - 1
+            });
+            for (collected, input) in groups[index].1.iter_mut().zip(inputs) {
+                collected.push(input);
+            }
+        }
+
+        // Aggregating nothing still yields one row when nothing is grouped on
+        let aggregates = query.items.iter().filter(|item| matches!(item.expr, Expr::Aggregate(..))).count();
+        if groups.is_empty() && aggregates == query.items.len() {
+            groups.push((Vec::new(), vec![Vec::new(); aggregates]));
+        }
+
+        for (key, collected) in groups {
+            let mut key = key.into_iter();
+            let mut collected = collected.into_iter();
+            let mut values = Vec::new();
+            for item in &query.items {
+                values.push(match &item.expr {
+                    Expr::Aggregate(aggregate, distinct, _) => {
+                        aggregate_values(*aggregate, *distinct, collected.next().unwrap_or_default())?
+                    }
+                    _ => key.next().unwrap_or(Value::Null),
+                });
+            }
+            projected.push((values, None));
+        }
+    } else {
+        for row in &rows {
+            let values = query.items.iter().map(|item| eval(&item.expr, row)).collect::<MadResult<Vec<_>>>()?;
+            projected.push((values, Some(row)));
+        }
+    }
+
+    if query.distinct {
+        let mut seen = HashSet::new();
+        projected.retain(|(values, _)| seen.insert(json_key(values)));
+    }
+
+    if !query.order.is_empty() {
+        let mut keyed = Vec::new();
+        for (values, row) in projected {
+            let keys = query.order.iter().map(|key| sort_value(query, key, &values, row)).collect::<MadResult<Vec<_>>>()?;
+            keyed.push((keys, values));
+        }
+        keyed.sort_by(|(a, _), (b, _)| {
+            query.order.iter().zip(a.iter().zip(b))
+                .map(|(key, (x, y))| if key.descending { sort_order(y, x) } else { sort_order(x, y) })
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        projected = keyed.into_iter().map(|(_, values)| (values, None)).collect();
+    }
+
+    let rows = projected.into_iter()
+        .skip(query.skip)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|(values, _)| values.iter().map(Value::to_json).collect())
+        .collect();
+
+    Ok(CypherResult {
+        columns: query.items.iter().map(|item| item.name.clone()).collect(),
+        rows,
+    })
+}
+
+// A returned column (by expression or alias), else the expression evaluated on the matched row
+fn sort_value<'g>(query: &Query, key: &SortKey, values: &[Value<'g>], row: Option<&Row<'g>>) -> MadResult<Value<'g>> {
+    let column = query.items.iter().position(|item| item.expr == key.expr)
+        .or_else(|| match &key.expr {
+            Expr::Var(name) => query.items.iter().position(|item| item.name == *name),
+            _ => None,
+        });
+    match (column, row) {
+        (Some(column), _) => Ok(values[column].clone()),
+        (None, Some(row)) => eval(&key.expr, row),
+        (None, None) => Err(cypher_error("ORDER BY must use returned columns when aggregating".to_string())),
+    }
+}
+
+fn aggregate_values(aggregate: Aggregate, distinct: bool, values: Vec<Value>) -> MadResult<Value> {
+    let mut values: Vec<Value> = values.into_iter().filter(|v| !matches!(v, Value::Null)).collect();
+    if distinct {
+        let mut seen = HashSet::new();
+        values.retain(|value| seen.insert(json_key(std::slice::from_ref(value))));
+    }
+
+    let numbers = |values: &[Value]| values.iter()
+        .map(|value| value.as_number().ok_or_else(|| cypher_error(format!("cannot sum a {}", value.type_name()))))
+        .collect::<MadResult<Vec<f64>>>();
+
+    Ok(match aggregate {
+        Aggregate::Count => Value::Number(values.len() as f64),
+        Aggregate::Collect => Value::List(values),
+        Aggregate::Sum => Value::Number(numbers(&values)?.iter().sum()),
+        Aggregate::Avg if values.is_empty() => Value::Null,
+        Aggregate::Avg => Value::Number(numbers(&values)?.iter().sum::<f64>() / values.len() as f64),
+        Aggregate::Min => values.into_iter().min_by(sort_order).unwrap_or(Value::Null),
+        Aggregate::Max => values.into_iter().max_by(sort_order).unwrap_or(Value::Null),
+    })
+}
+
+fn json_key(values: &[Value]) -> String {
+    serde_json::Value::Array(values.iter().map(Value::to_json).collect()).to_string()
+}
+
+// ---- Lexing ----
+
+// Longest first, so ".." wins over "."
+const SYMBOLS: [&str; 21] = [
+    "..", "<=", ">=", "<>", "!=", "(", ")", "[", "]", "{", "}", ",", ":", ".", "|", "*", "=", "<", ">", "-", ";",
+];
+
+fn tokenize(query: &str) -> MadResult<Vec<Spanned>> {
+    let mut tokens = Vec::new();
+    let chars: Vec<(usize, char)> = query.char_indices().collect();
+    let offset = |i: usize| chars.get(i).map(|(o, _)| *o).unwrap_or(query.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '/' if chars.get(i + 1).map(|(_, c)| *c) == Some('/') => {
+                while i < chars.len() && chars[i].1 != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '\'' | '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i).map(|(_, c)| *c) {
+                        Some(ch) if ch == c => break,
+                        Some('\\') => {
+                            i += 1;
+                            match chars.get(i).map(|(_, c)| *c) {
+                                Some('n') => value.push('\n'),
+                                Some('t') => value.push('\t'),
+                                Some('r') => value.push('\r'),
+                                Some('b') => value.push('\u{8}'),
+                                Some('f') => value.push('\u{c}'),
+                                Some('u') => {
+                                    let hex: String = chars.iter().skip(i + 1).take(4).map(|(_, c)| *c).collect();
+                                    let ch = u32::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 4).and_then(char::from_u32)
+                                        .ok_or_else(|| cypher_error(format!("bad escape \\u{}", hex)))?;
+                                    value.push(ch);
+                                    i += 4;
+                                }
+                                Some(other) => value.push(other),
+                                None => return Err(cypher_error("unterminated string".to_string())),
+                            }
+                        }
+                        Some(ch) => value.push(ch),
+                        None => return Err(cypher_error("unterminated string".to_string())),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                Token::Str(value)
+            }
+            '`' => {
+                // Quoted name; a doubled backtick stands for one
+                let mut name = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i).map(|(_, c)| *c) {
+                        Some('`') if chars.get(i + 1).map(|(_, c)| *c) == Some('`') => {
+                            name.push('`');
+                            i += 2;
+                        }
+                        Some('`') => break,
+                        Some(ch) => {
+                            name.push(ch);
+                            i += 1;
+                        }
+                        None => return Err(cypher_error("unterminated `name`".to_string())),
+                    }
+                }
+                i += 1;
+                Token::Ident(name)
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = i;
+                while end < chars.len() && chars[end].1.is_ascii_digit() {
+                    end += 1;
+                }
+                let digit_at = |at: usize| chars.get(at).is_some_and(|(_, c)| c.is_ascii_digit());
+                let mut float = false;
+                // A '.' only continues the number when a digit follows, so 1..3 is a range
+                if chars.get(end).map(|(_, c)| *c) == Some('.') && digit_at(end + 1) {
+                    float = true;
+                    end += 1;
+                    while digit_at(end) {
+                        end += 1;
+                    }
+                }
+                // Exponents: 1e-7, 2.5E+10
+                if matches!(chars.get(end).map(|(_, c)| *c), Some('e' | 'E')) {
+                    let sign = matches!(chars.get(end + 1).map(|(_, c)| *c), Some('+' | '-')) as usize;
+                    if digit_at(end + 1 + sign) {
+                        float = true;
+                        end += 1 + sign;
+                        while digit_at(end) {
+                            end += 1;
+                        }
+                    }
+                }
+                let text = &query[start..offset(end)];
+                i = end;
+                let n: f64 = text.parse().map_err(|_| cypher_error(format!("bad number '{}'", text)))?;
+                if float { Token::Float(n) } else { Token::Num(n) }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = i;
+                while end < chars.len() && (chars[end].1.is_alphanumeric() || chars[end].1 == '_') {
+                    end += 1;
+                }
+                let name = query[start..offset(end)].to_string();
+                i = end;
+                Token::Ident(name)
+            }
+            _ => {
+                let rest = &query[start..];
+                let symbol = SYMBOLS.iter().find(|s| rest.starts_with(**s))
+                    .ok_or_else(|| cypher_error(format!("unexpected character '{}'", c)))?;
+                i += symbol.chars().count();
+                Token::Sym(symbol)
+            }
+        };
+        tokens.push(Spanned { token, start, end: offset(i) });
+    }
+
+    Ok(tokens)
+}
+
+pub(crate) fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("'{}'", name),
+        Token::Str(s) => format!("string '{}'", s),
+        Token::Num(n) | Token::Float(n) => format!("number {}", n),
+        Token::Sym(s) => format!("'{}'", s),
+    }
+}
+
+pub(crate) fn cypher_error(message: String) -> MadError {
+    MadError::InvalidInput(format!("cypher: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn graph() -> GraphDatabase {
+        let mut graph = GraphDatabase::new();
+        graph.add_node("alice", "Person", r#"{"name": "Alice", "age": 34}"#).unwrap();
+        graph.add_node("bob", "Person", r#"{"name": "Bob", "age": 27}"#).unwrap();
+        graph.add_node("carol", "Person", r#"{"name": "Carol", "age": 41}"#).unwrap();
+        graph.add_node("d1", "Document", r#"{"year": 2021}"#).unwrap();
+        graph.add_node("d2", "Document", r#"{"year": 2018}"#).unwrap();
+        graph.add_edge("alice", "bob", "KNOWS", "").unwrap();
+        graph.add_edge("bob", "carol", "KNOWS", "").unwrap();
+        graph.add_edge("alice", "d1", "WROTE", "").unwrap();
+        graph.add_edge("alice", "d2", "WROTE", "").unwrap();
+        graph.add_edge("carol", "d1", "WROTE", "").unwrap();
+        graph
+    }
+
+    fn rows(query: &str) -> Vec<Vec<serde_json::Value>> {
+        execute(&graph(), query).unwrap().rows
+    }
+
+    #[test]
+    fn filters_aggregates_and_orders() {
+        let result = execute(&graph(), "MATCH (p:Person)-[:WROTE]->(d:Document) WHERE d.year >= 2020 \
+            RETURN p.name AS name, count(d) AS docs ORDER BY name").unwrap();
+        assert_eq!(result.columns, vec!["name", "docs"]);
+        assert_eq!(result.rows, vec![vec![json!("Alice"), json!(1)], vec![json!("Carol"), json!(1)]]);
+
+        assert_eq!(
+            rows("MATCH (p:Person) RETURN p.name ORDER BY p.age DESC SKIP 1 LIMIT 1"),
+            vec![vec![json!("Alice")]]
+        );
+        assert_eq!(rows("MATCH (p:Person {name: 'Bob'}) RETURN id(p)"), vec![vec![json!("bob")]]);
+    }
+
+    #[test]
+    fn matches_variable_length_paths() {
+        assert_eq!(
+            rows("MATCH path = (a {name: 'Alice'})-[:KNOWS*1..2]->(b) RETURN b.name, length(path) ORDER BY b.name"),
+            vec![vec![json!("Bob"), json!(1)], vec![json!("Carol"), json!(2)]]
+        );
+        assert_eq!(rows("MATCH (a)-[:KNOWS]-(b {name: 'Bob'}) RETURN count(a)"), vec![vec![json!(2)]]);
+    }
+
+    #[test]
+    fn keeps_json_types_in_comparisons() {
+        let mut graph = graph();
+        graph.add_node("e", "Person", r#"{"name": "Eve", "age": "34"}"#).unwrap();
+        let result = execute(&graph, "MATCH (p:Person) WHERE p.age = 34 RETURN p.name").unwrap();
+        assert_eq!(result.rows, vec![vec![json!("Alice")]]);
+        let result = execute(&graph, "MATCH (p:Person) WHERE p.age > 1.5e1 AND p.name STARTS WITH 'A' RETURN p.age").unwrap();
+        assert_eq!(result.rows, vec![vec![json!(34)]]);
+    }
+
+    #[test]
+    fn rejects_writes_and_bad_syntax() {
+        for query in ["CREATE (n:Person)", "MATCH (n) DELETE n", "MATCH (n RETURN n", "MATCH (n) RETURN m"] {
+            assert!(execute(&graph(), query).is_err(), "{}", query);
+        }
+    }
+}
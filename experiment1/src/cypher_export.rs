@@ -0,0 +1,479 @@
+use crate::cypher::{cypher_error, describe, Parser, Token};
+use crate::error::MadResult;
+use crate::graph_db::{check_list_values, GraphEdge, GraphNode, GraphSnapshot};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// Cypher scripts for loading a graph into Neo4j or Memgraph, and an importer
+// that reads them back. Labels, relationship types and property keys are
+// backtick-quoted, strings are escaped, and node and edge IDs are stored in
+// a reserved `_mad_id` property, so stored properties (`id` included) are
+// kept as they are. Lookups by ID are much faster with an index on
+// :Label(_mad_id) in the target. Graphs holding lists the targets can't
+// store (mixed types or nulls) fail to export rather than writing a script
+// that won't load.
+
+pub const ID_PROPERTY: &str = "_mad_id";
+
+// A parameterized statement for a database driver: `UNWIND $rows AS row ...`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CypherStatement {
+    pub query: String,
+    pub parameters: serde_json::Value,
+}
+
+// One CREATE per node, then one MATCH ... CREATE per edge
+pub fn export_statements(snapshot: &GraphSnapshot) -> MadResult<String> {
+    check_snapshot(snapshot)?;
+    let labels: HashMap<&str, &str> = snapshot.nodes.iter().map(|n| (n.id.as_str(), n.label.as_str())).collect();
+    let mut statements = Vec::new();
+
+    for node in &snapshot.nodes {
+        statements.push(format!(
+            "CREATE ({})",
+            [label_suffix(&node.label), map_literal(&node.id, &node.properties)].join(" ").trim_start()
+        ));
+    }
+
+    for edge in &snapshot.edges {
+        statements.push(format!(
+            "MATCH (a{} {{{}: {}}}), (b{} {{{}: {}}}) CREATE (a)-[:{} {}]->(b)",
+            label_suffix(labels.get(edge.from.as_str()).copied().unwrap_or_default()),
+            ID_PROPERTY,
+            string_literal(&edge.from),
+            label_suffix(labels.get(edge.to.as_str()).copied().unwrap_or_default()),
+            ID_PROPERTY,
+            string_literal(&edge.to),
+            quote_name(&edge.relationship),
+            map_literal(&edge.id, &edge.properties)
+        ));
+    }
+
+    Ok(join_statements(statements))
+}
+
+// The batches with their rows written inline, for cypher-shell and similar
+pub fn export_batched(snapshot: &GraphSnapshot, batch_size: usize) -> MadResult<String> {
+    let statements = batches(snapshot, batch_size)?.into_iter()
+        .map(|statement| {
+            let rows = statement.parameters.get("rows").map(value_literal).unwrap_or_else(|| "[]".to_string());
+            statement.query.replacen("$rows", &rows, 1)
+        })
+        .collect();
+    Ok(join_statements(statements))
+}
+
+// UNWIND statements creating up to `batch_size` nodes of one label, or edges of
+// one relationship type between two labels, at a time
+pub fn batches(snapshot: &GraphSnapshot, batch_size: usize) -> MadResult<Vec<CypherStatement>> {
+    check_snapshot(snapshot)?;
+    let batch_size = batch_size.max(1);
+    let mut statements = Vec::new();
+
+    let mut nodes_by_label: BTreeMap<&str, Vec<&GraphNode>> = BTreeMap::new();
+    for node in &snapshot.nodes {
+        nodes_by_label.entry(&node.label).or_default().push(node);
+    }
+    for (label, nodes) in nodes_by_label {
+        let query = format!(
+            "UNWIND $rows AS row\nCREATE (n{}) SET n = row.properties, n.{} = row.id",
+            label_suffix(label),
+            ID_PROPERTY
+        );
+        for chunk in nodes.chunks(batch_size) {
+            let rows: Vec<serde_json::Value> = chunk.iter()
+                .map(|node| serde_json::json!({ "id": node.id, "properties": sorted(&node.properties) }))
+                .collect();
+            statements.push(CypherStatement { query: query.clone(), parameters: serde_json::json!({ "rows": rows }) });
+        }
+    }
+
+    let labels: HashMap<&str, &str> = snapshot.nodes.iter().map(|n| (n.id.as_str(), n.label.as_str())).collect();
+    let mut edges_by_kind: BTreeMap<(&str, &str, &str), Vec<&GraphEdge>> = BTreeMap::new();
+    for edge in &snapshot.edges {
+        let from = labels.get(edge.from.as_str()).copied().unwrap_or_default();
+        let to = labels.get(edge.to.as_str()).copied().unwrap_or_default();
+        edges_by_kind.entry((from, edge.relationship.as_str(), to)).or_default().push(edge);
+    }
+    for ((from, relationship, to), edges) in edges_by_kind {
+        let query = format!(
+            "UNWIND $rows AS row\nMATCH (a{} {{{id}: row.from}}), (b{} {{{id}: row.to}})\nCREATE (a)-[r:{}]->(b) SET r = row.properties, r.{id} = row.id",
+            label_suffix(from),
+            label_suffix(to),
+            quote_name(relationship),
+            id = ID_PROPERTY
+        );
+        for chunk in edges.chunks(batch_size) {
+            let rows: Vec<serde_json::Value> = chunk.iter()
+                .map(|edge| serde_json::json!({
+                    "id": edge.id,
+                    "from": edge.from,
+                    "to": edge.to,
+                    "properties": sorted(&edge.properties)
+                }))
+                .collect();
+            statements.push(CypherStatement { query: query.clone(), parameters: serde_json::json!({ "rows": rows }) });
+        }
+    }
+
+    Ok(statements)
+}
+
+// Documents saved before list values were checked may still hold such lists
+fn check_snapshot(snapshot: &GraphSnapshot) -> MadResult<()> {
+    for node in &snapshot.nodes {
+        check_list_values(&node.properties)?;
+    }
+    for edge in &snapshot.edges {
+        check_list_values(&edge.properties)?;
+    }
+    Ok(())
+}
+
+fn join_statements(statements: Vec<String>) -> String {
+    statements.into_iter().map(|statement| statement + ";\n").collect()
+}
+
+// Null properties are left out, as the target databases don't store them.
+// ID_PROPERTY is reserved, so no stored property can clash with it.
+fn sorted(properties: &HashMap<String, serde_json::Value>) -> BTreeMap<&str, &serde_json::Value> {
+    properties.iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| (key.as_str(), value))
+        .collect()
+}
+
+// `{_mad_id: '...', `key`: '...'}` with keys in sorted order
+fn map_literal(id: &str, properties: &HashMap<String, serde_json::Value>) -> String {
+    let mut entries = vec![format!("{}: {}", ID_PROPERTY, string_literal(id))];
+    entries.extend(sorted(properties).into_iter()
+        .map(|(key, value)| format!("{}: {}", quote_name(key), value_literal(value))));
+    format!("{{{}}}", entries.join(", "))
+}
+
+fn label_suffix(label: &str) -> String {
+    if label.is_empty() {
+        String::new()
+    } else {
+        format!(":{}", quote_name(label))
+    }
+}
+
+pub fn quote_name(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+pub fn string_literal(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('\'');
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+fn value_literal(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => string_literal(s),
+        serde_json::Value::Array(items) => format!("[{}]", items.iter().map(value_literal).collect::<Vec<_>>().join(", ")),
+        serde_json::Value::Object(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(key, value)| format!("{}: {}", quote_name(key), value_literal(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        other => other.to_string(),
+    }
+}
+
+// Reads scripts written by export_statements or export_batched
+pub fn import(script: &str) -> MadResult<GraphSnapshot> {
+    let mut parser = Parser::new(script)?;
+    let mut snapshot = GraphSnapshot { nodes: Vec::new(), edges: Vec::new() };
+
+    while parser.peek().is_some() {
+        if parser.sym(";") {
+            continue;
+        }
+        if parser.keyword("CREATE") {
+            let (label, mut properties) = node_pattern(&mut parser)?;
+            let id = take_id(&mut properties)?;
+            snapshot.nodes.push(GraphNode { id, label, properties });
+        } else if parser.keyword("MATCH") {
+            let (_, mut from) = node_pattern(&mut parser)?;
+            parser.expect(",")?;
+            let (_, mut to) = node_pattern(&mut parser)?;
+            parser.expect_keyword("CREATE")?;
+            let (relationship, mut properties) = relationship_pattern(&mut parser)?;
+            snapshot.edges.push(GraphEdge {
+                id: take_id(&mut properties)?,
+                from: take_id(&mut from)?,
+                to: take_id(&mut to)?,
+                relationship,
+                properties,
+            });
+        } else if parser.keyword("UNWIND") {
+            unwind(&mut parser, &mut snapshot)?;
+        } else {
+            let token = parser.next()?;
+            return Err(cypher_error(format!("unexpected {} in script", describe(&token))));
+        }
+    }
+
+    Ok(snapshot)
+}
+
+// UNWIND [...] AS row, followed by one of the two batch forms
+fn unwind(parser: &mut Parser, snapshot: &mut GraphSnapshot) -> MadResult<()> {
+    let rows = match literal(parser)? {
+        serde_json::Value::Array(rows) => rows,
+        _ => return Err(cypher_error("UNWIND needs a list of rows".to_string())),
+    };
+    parser.expect_keyword("AS")?;
+    parser.ident()?;
+
+    let field = |row: &serde_json::Value, key: &str| -> MadResult<String> {
+        row.get(key).map(value_text).ok_or_else(|| cypher_error(format!("row without '{}'", key)))
+    };
+    let properties = |row: &serde_json::Value| property_map(row.get("properties").cloned().unwrap_or_default());
+
+    if parser.keyword("CREATE") {
+        let (label, _) = node_pattern(parser)?;
+        for row in &rows {
+            snapshot.nodes.push(GraphNode { id: field(row, "id")?, label: label.clone(), properties: properties(row) });
+        }
+    } else {
+        parser.expect_keyword("MATCH")?;
+        node_pattern(parser)?;
+        parser.expect(",")?;
+        node_pattern(parser)?;
+        parser.expect_keyword("CREATE")?;
+        let (relationship, _) = relationship_pattern(parser)?;
+        for row in &rows {
+            snapshot.edges.push(GraphEdge {
+                id: field(row, "id")?,
+                from: field(row, "from")?,
+                to: field(row, "to")?,
+                relationship: relationship.clone(),
+                properties: properties(row),
+            });
+        }
+    }
+
+    // SET n = row.properties, n._mad_id = row.id
+    parser.expect_keyword("SET")?;
+    while parser.peek().is_some_and(|token| *token != Token::Sym(";")) {
+        parser.next()?;
+    }
+    Ok(())
+}
+
+// `(var:Label {map})`; a `row.x` value reads as null
+fn node_pattern(parser: &mut Parser) -> MadResult<(String, HashMap<String, serde_json::Value>)> {
+    parser.expect("(")?;
+    if let Some(Token::Ident(_)) = parser.peek() {
+        parser.ident()?;
+    }
+    let label = if parser.sym(":") { parser.ident()? } else { String::new() };
+    let properties = if parser.peek() == Some(&Token::Sym("{")) { property_map(literal(parser)?) } else { HashMap::new() };
+    parser.expect(")")?;
+    Ok((label, properties))
+}
+
+// `(a)-[var:TYPE {map}]->(b)`
+fn relationship_pattern(parser: &mut Parser) -> MadResult<(String, HashMap<String, serde_json::Value>)> {
+    parser.expect("(")?;
+    parser.ident()?;
+    parser.expect(")")?;
+    parser.expect("-")?;
+    parser.expect("[")?;
+    if let Some(Token::Ident(_)) = parser.peek() {
+        parser.ident()?;
+    }
+    parser.expect(":")?;
+    let relationship = parser.ident()?;
+    let properties = if parser.peek() == Some(&Token::Sym("{")) { property_map(literal(parser)?) } else { HashMap::new() };
+    parser.expect("]")?;
+    parser.expect("-")?;
+    parser.expect(">")?;
+    parser.expect("(")?;
+    parser.ident()?;
+    parser.expect(")")?;
+    Ok((relationship, properties))
+}
+
+fn literal(parser: &mut Parser) -> MadResult<serde_json::Value> {
+    Ok(match parser.next()? {
+        Token::Str(s) => serde_json::Value::String(s),
+        Token::Num(n) => number(n),
+        Token::Float(n) => serde_json::Value::from(n),
+        Token::Sym("-") => match parser.next()? {
+            Token::Num(n) => number(-n),
+            Token::Float(n) => serde_json::Value::from(-n),
+            token => return Err(cypher_error(format!("expected a number, found {}", describe(&token)))),
+        },
+        Token::Sym("[") => {
+            let mut items = Vec::new();
+            if !parser.sym("]") {
+                loop {
+                    items.push(literal(parser)?);
+                    if !parser.sym(",") {
+                        break;
+                    }
+                }
+                parser.expect("]")?;
+            }
+            serde_json::Value::Array(items)
+        }
+        Token::Sym("{") => {
+            let mut map = serde_json::Map::new();
+            if !parser.sym("}") {
+                loop {
+                    let key = match parser.next()? {
+                        Token::Ident(key) | Token::Str(key) => key,
+                        token => return Err(cypher_error(format!("expected a key, found {}", describe(&token)))),
+                    };
+                    parser.expect(":")?;
+                    map.insert(key, literal(parser)?);
+                    if !parser.sym(",") {
+                        break;
+                    }
+                }
+                parser.expect("}")?;
+            }
+            serde_json::Value::Object(map)
+        }
+        Token::Ident(word) => match word.to_ascii_lowercase().as_str() {
+            "true" => serde_json::Value::Bool(true),
+            "false" => serde_json::Value::Bool(false),
+            "null" => serde_json::Value::Null,
+            // row.from and friends inside batch patterns
+            _ if parser.sym(".") => {
+                parser.ident()?;
+                serde_json::Value::Null
+            }
+            _ => return Err(cypher_error(format!("unexpected '{}' in a literal", word))),
+        },
+        token => return Err(cypher_error(format!("unexpected {} in a literal", describe(&token)))),
+    })
+}
+
+// Integers stay integers; floats are written with a fraction or exponent
+fn number(n: f64) -> serde_json::Value {
+    if n.fract() == 0.0 && n.abs() < 9.0e15 {
+        serde_json::Value::from(n as i64)
+    } else {
+        serde_json::Value::from(n)
+    }
+}
+
+// IDs are strings even when a script writes them as numbers
+fn value_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn property_map(value: serde_json::Value) -> HashMap<String, serde_json::Value> {
+    match value {
+        serde_json::Value::Object(map) => map.into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn take_id(properties: &mut HashMap<String, serde_json::Value>) -> MadResult<String> {
+    properties.remove(ID_PROPERTY)
+        .map(|id| value_text(&id))
+        .ok_or_else(|| cypher_error(format!("missing '{}' property", ID_PROPERTY)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::MadError;
+    use crate::graph_db::GraphDatabase;
+
+    fn graph() -> GraphDatabase {
+        let mut graph = GraphDatabase::new();
+        graph.add_node("n'1", "Odd`Label", r#"{"id": "own id", "text": "it's\n\"quoted\" \\ ok", "ratio": 2.0}"#).unwrap();
+        graph.add_node("n2", "Plain", r#"{"tiny": 1e-7, "count": -3, "flags": [true, false]}"#).unwrap();
+        graph.add_edge_with_id("e1", "n'1", "n2", "LINKS TO", r#"{"weight": 0.5}"#).unwrap();
+        graph
+    }
+
+    // Property maps compared regardless of order
+    fn snapshot(graph: &GraphDatabase) -> serde_json::Value {
+        serde_json::to_value(graph.snapshot()).unwrap()
+    }
+
+    #[test]
+    fn both_formats_round_trip() {
+        let graph = graph();
+        for script in [graph.export_cypher().unwrap(), graph.export_cypher_batched(1).unwrap()] {
+            let loaded = GraphDatabase::import_cypher(&script).unwrap();
+            assert_eq!(snapshot(&loaded), snapshot(&graph), "{}", script);
+        }
+    }
+
+    #[test]
+    fn escapes_names_and_strings() {
+        assert_eq!(quote_name("a`b"), "`a``b`");
+        assert_eq!(string_literal("it's\\\n"), "'it\\'s\\\\\\n'");
+
+        let script = graph().export_cypher().unwrap();
+        assert!(script.contains("`Odd``Label`"));
+        assert!(script.contains("`LINKS TO`"));
+        assert!(script.contains("_mad_id: 'n\\'1'"));
+    }
+
+    #[test]
+    fn batches_carry_rows_as_parameters() {
+        let batches = graph().cypher_batches(1).unwrap();
+        assert_eq!(batches.len(), 3);
+        assert!(batches[0].query.starts_with("UNWIND $rows AS row"));
+        assert_eq!(batches[0].parameters["rows"][0]["id"], "n'1");
+        assert_eq!(batches[2].parameters["rows"][0]["properties"]["weight"], 0.5);
+    }
+
+    #[test]
+    fn export_rejects_lists_targets_cannot_store() {
+        for flags in [r#"[true, null]"#, r#"[1, "x"]"#] {
+            let snapshot = format!(
+                r#"{{"nodes": [{{"id": "n", "label": "N", "properties": {{"flags": {}}}}}], "edges": []}}"#,
+                flags
+            );
+            let graph = GraphDatabase::import_json(&snapshot).unwrap();
+            assert!(matches!(graph.export_cypher(), Err(MadError::InvalidInput(_))));
+            assert!(matches!(graph.export_cypher_batched(10), Err(MadError::InvalidInput(_))));
+            assert!(matches!(graph.cypher_batches(10), Err(MadError::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn import_rejects_other_statements() {
+        assert!(import("MATCH (n) DETACH DELETE n").is_err());
+        assert!(import("CREATE (:`N` {name: 'x'})").is_err());
+        assert!(import("CREATE (:`N` {_mad_id: 'x'}) RETURN 1").is_err());
+    }
+
+    #[test]
+    fn id_property_is_reserved() {
+        let mut graph = GraphDatabase::new();
+        assert!(graph.add_node("x", "N", r#"{"_mad_id": "y"}"#).is_err());
+        graph.add_node("x", "N", "").unwrap();
+        assert!(graph.update_node_properties("x", r#"{"_mad_id": "y"}"#).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::error::MadResult;
+#[cfg(feature = "http-embeddings")]
+use crate::error::MadError;
+use crate::text_index;
+
+// Turns text into embeddings. The model name and dimension are recorded in
+// the document metadata so queries can later be embedded the same way.
+pub trait EmbeddingProvider {
+    fn model(&self) -> &str;
+    fn dimension(&self) -> usize;
+    fn embed(&self, texts: &[&str]) -> MadResult<Vec<Vec<f32>>>;
+}
+
+// Offline, deterministic embeddings by feature hashing: stemmed terms and
+// term bigrams are hashed into signed buckets and the result L2-normalized.
+// Lexical rather than semantic, but stable across platforms and releases.
+pub struct HashedEmbeddingProvider {
+    dimension: usize,
+}
+
+impl HashedEmbeddingProvider {
+    pub const MODEL: &'static str = "mad-hashed-bow-v1";
+    pub const DEFAULT_DIMENSION: usize = 384;
+
+    pub fn new(dimension: usize) -> HashedEmbeddingProvider {
+        HashedEmbeddingProvider { dimension: dimension.max(1) }
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimension];
+        let terms = text_index::terms(text);
+
+        let mut add = |feature: &str, weight: f32| {
+            let hash = fnv1a(feature.as_bytes());
+            let bucket = (hash % self.dimension as u64) as usize;
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[bucket] += sign * weight;
+        };
+        for term in &terms {
+            add(term, 1.0);
+        }
+        for pair in terms.windows(2) {
+            add(&format!("{} {}", pair[0], pair[1]), 0.5);
+        }
+
+        crate::vector_index::normalize(&vector)
+    }
+}
+
+impl Default for HashedEmbeddingProvider {
+    fn default() -> Self {
+        HashedEmbeddingProvider::new(HashedEmbeddingProvider::DEFAULT_DIMENSION)
+    }
+}
+
+impl EmbeddingProvider for HashedEmbeddingProvider {
+    fn model(&self) -> &str {
+        HashedEmbeddingProvider::MODEL
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, texts: &[&str]) -> MadResult<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+// 64-bit FNV-1a, used instead of std's hasher because its output is specified
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// Calls an embedding service with an OpenAI-style request body
+// ({"model", "input": [...]}) and accepts either {"data": [{"embedding", "index"}]}
+// or {"embeddings": [[...]]} back, which covers most hosted and local servers.
+#[cfg(feature = "http-embeddings")]
+pub struct HttpEmbeddingProvider {
+    endpoint: String,
+    model: String,
+    dimension: usize,
+    api_key: Option<String>,
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "http-embeddings")]
+impl HttpEmbeddingProvider {
+    pub fn new(endpoint: &str, model: &str, dimension: usize) -> HttpEmbeddingProvider {
+        HttpEmbeddingProvider {
+            endpoint: endpoint.to_string(),
+            model: model.to_string(),
+            dimension,
+            api_key: None,
+            agent: ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(60))
+                .build(),
+        }
+    }
+
+    // Sent as a bearer token
+    pub fn with_api_key(mut self, api_key: &str) -> HttpEmbeddingProvider {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    fn parse_response(&self, response: serde_json::Value, expected: usize) -> MadResult<Vec<Vec<f32>>> {
+        let to_vector = |value: &serde_json::Value| -> MadResult<Vec<f32>> {
+            let vector: Vec<f32> = serde_json::from_value(value.clone())?;
+            if vector.len() != self.dimension {
+                return Err(MadError::DimensionMismatch { expected: self.dimension, actual: vector.len() });
+            }
+            Ok(vector)
+        };
+
+        let vectors = if let Some(data) = response.get("data").and_then(|d| d.as_array()) {
+            let mut data: Vec<&serde_json::Value> = data.iter().collect();
+            data.sort_by_key(|entry| entry.get("index").and_then(|i| i.as_u64()).unwrap_or(0));
+            data.iter()
+                .map(|entry| to_vector(entry.get("embedding").unwrap_or(&serde_json::Value::Null)))
+                .collect::<MadResult<Vec<_>>>()?
+        } else if let Some(embeddings) = response.get("embeddings").and_then(|e| e.as_array()) {
+            embeddings.iter().map(to_vector).collect::<MadResult<Vec<_>>>()?
+        } else {
+            return Err(MadError::Provider("response has neither 'data' nor 'embeddings'".to_string()));
+        };
+
+        if vectors.len() != expected {
+            return Err(MadError::Provider(format!(
+                "asked for {} embeddings, got {}",
+                expected,
+                vectors.len()
+            )));
+        }
+        Ok(vectors)
+    }
+}
+
+#[cfg(feature = "http-embeddings")]
+impl EmbeddingProvider for HttpEmbeddingProvider {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, texts: &[&str]) -> MadResult<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut request = self.agent.post(&self.endpoint);
+        if let Some(api_key) = &self.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", api_key));
+        }
+        let response = request
+            .send_json(serde_json::json!({ "model": self.model, "input": texts }))
+            .map_err(|e| MadError::Provider(e.to_string()))?;
+        let body: serde_json::Value = response.into_json()
+            .map_err(|e| MadError::Provider(format!("{}: {}", self.endpoint, e)))?;
+
+        self.parse_response(body, texts.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn hashed_embeddings_are_deterministic_and_normalized() {
+        let provider = HashedEmbeddingProvider::new(64);
+        let vectors = provider.embed(&["graph databases", "graph databases"]).unwrap();
+        assert_eq!(vectors[0], vectors[1]);
+        assert_eq!(vectors[0].len(), 64);
+        assert!((cosine(&vectors[0], &vectors[0]) - 1.0).abs() < 1e-5);
+        assert_eq!(provider.model(), HashedEmbeddingProvider::MODEL);
+    }
+
+    #[test]
+    fn shared_terms_score_higher() {
+        let provider = HashedEmbeddingProvider::new(256);
+        let query = provider.embed_text("vector search");
+        let related = provider.embed_text("searching vectors quickly");
+        let unrelated = provider.embed_text("banana bread recipe");
+        assert!(cosine(&query, &related) > cosine(&query, &unrelated));
+    }
+    #[cfg(feature = "http-embeddings")]
+    #[test]
+    fn http_responses_parse_in_both_forms() {
+        use serde_json::json;
+        let provider = HttpEmbeddingProvider::new("http://localhost:1/embeddings", "test-model", 2);
+
+        let data = json!({"data": [
+            {"index": 1, "embedding": [0.0, 1.0]},
+            {"index": 0, "embedding": [1.0, 0.0]}
+        ]});
+        assert_eq!(provider.parse_response(data, 2).unwrap(), vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+
+        let bare = json!({"embeddings": [[0.5, 0.5], [0.25, 0.75]]});
+        assert_eq!(provider.parse_response(bare, 2).unwrap(), vec![vec![0.5, 0.5], vec![0.25, 0.75]]);
+
+        let short = json!({"embeddings": [[0.5, 0.5]]});
+        assert!(matches!(provider.parse_response(short, 2), Err(MadError::Provider(_))));
+        let wrong_dimension = json!({"embeddings": [[0.5, 0.5, 0.5]]});
+        assert!(matches!(provider.parse_response(wrong_dimension, 1), Err(MadError::DimensionMismatch { .. })));
+        assert!(matches!(provider.parse_response(json!({"vectors": []}), 0), Err(MadError::Provider(_))));
+    }
+}
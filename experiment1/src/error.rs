@@ -0,0 +1,44 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MadError {
+    NotFound(String),
+    AlreadyExists(String),
+    InvalidJson(String),
+    InvalidInput(String),
+    DimensionMismatch { expected: usize, actual: usize },
+    IntegrityFailure(String),
+    Unsupported(String),
+    Expired(String),
+    Container(String),
+    Provider(String),
+}
+
+pub type MadResult<T> = Result<T, MadError>;
+
+impl fmt::Display for MadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MadError::NotFound(what) => write!(f, "{} not found", what),
+            MadError::AlreadyExists(what) => write!(f, "{} already exists", what),
+            MadError::InvalidJson(msg) => write!(f, "Invalid JSON: {}", msg),
+            MadError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            MadError::DimensionMismatch { expected, actual } => {
+                write!(f, "Dimension mismatch: expected {}, got {}", expected, actual)
+            }
+            MadError::IntegrityFailure(msg) => write!(f, "Integrity check failed: {}", msg),
+            MadError::Unsupported(msg) => write!(f, "Unsupported: {}", msg),
+            MadError::Expired(what) => write!(f, "{} has expired", what),
+            MadError::Container(msg) => write!(f, "Container error: {}", msg),
+            MadError::Provider(msg) => write!(f, "Embedding provider error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MadError {}
+
+impl From<serde_json::Error> for MadError {
+    fn from(e: serde_json::Error) -> Self {
+        MadError::InvalidJson(e.to_string())
+    }
+}
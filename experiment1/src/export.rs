@@ -0,0 +1,344 @@
+use crate::chunker::PARENT_ID_KEY;
+use crate::error::MadResult;
+use crate::graph_db::GraphSnapshot;
+use crate::{ContentItem, DocumentMetadata, MadDocument, VectorEmbedding};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+
+// Complete JSON export: everything needed to rebuild the document
+#[derive(Serialize, Deserialize)]
+pub struct DocumentExport {
+    pub format_version: String,
+    pub metadata: DocumentMetadata,
+    pub content_items: Vec<ContentItem>,
+    pub vectors: Vec<VectorEmbedding>,
+    pub graph: GraphSnapshot,
+}
+
+pub fn to_json(doc: &MadDocument) -> MadResult<String> {
+    let export = DocumentExport {
+        format_version: crate::container::FORMAT_VERSION.to_string(),
+        metadata: doc.metadata().clone(),
+        content_items: doc.content_items().to_vec(),
+        vectors: doc.vectors().to_vec(),
+        graph: doc.graph().snapshot(),
+    };
+
+    Ok(serde_json::to_string_pretty(&export)?)
+}
+
+pub fn to_markdown(doc: &MadDocument, include_metadata: bool) -> String {
+    let meta = doc.metadata();
+    let mut out = String::new();
+
+    if include_metadata {
+        out.push_str(&format!("# {}\n\n", meta.title));
+        out.push_str(&format!("- **Author:** {}\n", meta.author));
+        out.push_str(&format!("- **Document ID:** {}\n", meta.id));
+        out.push_str(&format!("- **Created:** {}\n", meta.created));
+        if !meta.content_hash.is_empty() {
+            out.push_str(&format!("- **Content hash:** {}\n", meta.content_hash));
+        }
+        out.push('\n');
+    }
+
+    let blocks: Vec<String> = readable_items(doc)
+        .map(markdown_block)
+        .filter(|block| !block.is_empty())
+        .collect();
+    out.push_str(&blocks.join("\n\n"));
+    out.push('\n');
+    out
+}
+
+pub fn to_html(doc: &MadDocument, include_metadata: bool) -> String {
+    let meta = doc.metadata();
+    let mut body = String::new();
+
+    if include_metadata {
+        body.push_str(&format!(
+            "<header>\n<h1>{}</h1>\n<dl>\n<dt>Author</dt><dd>{}</dd>\n<dt>Document ID</dt><dd>{}</dd>\n<dt>Created</dt><dd>{}</dd>\n</dl>\n</header>\n",
+            escape_html(&meta.title),
+            escape_html(&meta.author),
+            escape_html(&meta.id),
+            meta.created
+        ));
+    }
+
+    for item in readable_items(doc) {
+        body.push_str(&format!(
+            "<section id=\"{}\" data-content-type=\"{}\">\n{}\n</section>\n",
+            escape_html(&item.id),
+            escape_html(&item.content_type),
+            html_block(item)
+        ));
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\nbody {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; line-height: 1.5; }}\nimg {{ max-width: 100%; }}\npre {{ background: #f4f4f4; padding: 1em; overflow-x: auto; }}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&meta.title),
+        body
+    )
+}
+
+fn markdown_block(item: &ContentItem) -> String {
+    match item.content_type.as_str() {
+        "text/markdown" => item_text(item),
+        t if t.starts_with("image/") => format!(
+            "![{}](data:{};base64,{})",
+            item.text_content.replace(']', "\\]"),
+            item.content_type,
+            item.data
+        ),
+        t if t.starts_with("text/") => item.text_content.clone(),
+        _ => format!("*[Attachment: {} — {}]*", item.content_type, item.text_content),
+    }
+}
+
+// `text/html` items are inserted as they are, scripts included; sanitize the
+// output before showing exports of untrusted documents in a browser
+fn html_block(item: &ContentItem) -> String {
+    match item.content_type.as_str() {
+        "text/html" => item_text(item),
+        "text/markdown" => markdown_to_html(&item_text(item)),
+        t if t.starts_with("image/") => format!(
+            "<figure>\n<img src=\"data:{};base64,{}\" alt=\"{}\">\n<figcaption>{}</figcaption>\n</figure>",
+            escape_html(&item.content_type),
+            item.data,
+            escape_html(&item.text_content),
+            escape_html(&item.text_content)
+        ),
+        t if t.starts_with("text/") => paragraphs_to_html(&item.text_content),
+        _ => format!(
+            "<p class=\"attachment\">Attachment ({}): {}</p>",
+            escape_html(&item.content_type),
+            escape_html(&item.text_content)
+        ),
+    }
+}
+
+// Original source for text items, falling back to the extracted text
+// Chunks repeat their parent's text, so readable exports leave them out
+fn readable_items(doc: &MadDocument) -> impl Iterator<Item = &ContentItem> {
+    doc.content_items().iter().filter(|item| !item.metadata.contains_key(PARENT_ID_KEY))
+}
+
+pub(crate) fn item_text(item: &ContentItem) -> String {
+    BASE64.decode(&item.data).ok()
+        .and_then(|data| String::from_utf8(data).ok())
+        .filter(|text| !text.is_empty())
+        .unwrap_or_else(|| item.text_content.clone())
+}
+
+fn paragraphs_to_html(text: &str) -> String {
+    text.split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>", escape_html(p).replace('\n', "<br>\n")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Minimal Markdown renderer: headings, fenced code, lists, paragraphs and
+// inline code/bold/italic/links. Enough for document bodies, not CommonMark.
+fn markdown_to_html(markdown: &str) -> String {
+    let mut html = Vec::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut list: Option<(&str, Vec<String>)> = None;
+    let mut code: Option<Vec<String>> = None;
+
+    fn flush_paragraph(html: &mut Vec<String>, paragraph: &mut Vec<String>) {
+        if !paragraph.is_empty() {
+            html.push(format!("<p>{}</p>", render_inline(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+    }
+
+    fn flush_list(html: &mut Vec<String>, list: &mut Option<(&str, Vec<String>)>) {
+        if let Some((tag, items)) = list.take() {
+            let items: Vec<String> = items.iter()
+                .map(|item| format!("<li>{}</li>", render_inline(item)))
+                .collect();
+            html.push(format!("<{}>\n{}\n</{}>", tag, items.join("\n"), tag));
+        }
+    }
+
+    for line in markdown.lines() {
+        if let Some(ref mut code_lines) = code {
+            if line.trim_start().starts_with("```") {
+                html.push(format!("<pre><code>{}</code></pre>", escape_html(&code_lines.join("\n"))));
+                code = None;
+            } else {
+                code_lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+            code = Some(Vec::new());
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+            continue;
+        }
+
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            flush_paragraph(&mut html, &mut paragraph);
+            flush_list(&mut html, &mut list);
+            html.push(format!("<h{}>{}</h{}>", level, render_inline(trimmed[level..].trim()), level));
+            continue;
+        }
+
+        let list_item = if let Some(rest) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+            Some(("ul", rest))
+        } else {
+            trimmed.split_once(". ")
+                .filter(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+                .map(|(_, rest)| ("ol", rest))
+        };
+
+        if let Some((tag, rest)) = list_item {
+            flush_paragraph(&mut html, &mut paragraph);
+            if list.as_ref().map(|(current, _)| *current != tag).unwrap_or(false) {
+                flush_list(&mut html, &mut list);
+            }
+            list.get_or_insert((tag, Vec::new())).1.push(rest.to_string());
+            continue;
+        }
+
+        flush_list(&mut html, &mut list);
+        paragraph.push(trimmed.to_string());
+    }
+
+    if let Some(code_lines) = code {
+        html.push(format!("<pre><code>{}</code></pre>", escape_html(&code_lines.join("\n"))));
+    }
+    flush_paragraph(&mut html, &mut paragraph);
+    flush_list(&mut html, &mut list);
+
+    html.join("\n")
+}
+
+fn render_inline(text: &str) -> String {
+    let escaped = escape_html(text);
+    let mut out = String::new();
+    let mut rest = escaped.as_str();
+
+    while let Some(pos) = rest.find(['`', '*', '[']) {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(inner) = rest.strip_prefix('`') {
+            if let Some(end) = inner.find('`') {
+                out.push_str(&format!("<code>{}</code>", &inner[..end]));
+                rest = &inner[end + 1..];
+                continue;
+            }
+        } else if let Some(inner) = rest.strip_prefix("**") {
+            if let Some(end) = inner.find("**") {
+                out.push_str(&format!("<strong>{}</strong>", &inner[..end]));
+                rest = &inner[end + 2..];
+                continue;
+            }
+        } else if let Some(inner) = rest.strip_prefix('*') {
+            if let Some(end) = inner.find('*') {
+                out.push_str(&format!("<em>{}</em>", &inner[..end]));
+                rest = &inner[end + 1..];
+                continue;
+            }
+        } else if let Some(inner) = rest.strip_prefix('[') {
+            if let Some((label, after)) = inner.split_once("](") {
+                if let Some(end) = after.find(')') {
+                    let target = &after[..end];
+                    if safe_link(target) {
+                        out.push_str(&format!("<a href=\"{}\">{}</a>", target, label));
+                    } else {
+                        out.push_str(label);
+                    }
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+        }
+
+        // Not a complete inline element; emit the marker literally
+        out.push_str(&rest[..1]);
+        rest = &rest[1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+// Only http(s), mailto and relative targets become links, so a `javascript:`
+// or `data:` target can't run anything when the export is opened
+fn safe_link(target: &str) -> bool {
+    let scheme_end = target.find(['/', '?', '#']).unwrap_or(target.len());
+    match target[..scheme_end].split_once(':') {
+        Some((scheme, _)) => ["http", "https", "mailto"].iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+        None => true,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> MadDocument {
+        let mut doc = MadDocument::new("Notes <draft>", "Ada");
+        doc.add_content("text/markdown", b"# Title\n\nSome **bold** text and `code`.", "Title Some bold text").unwrap();
+        doc.add_content("text/plain", b"", "Plain <b>not html</b>").unwrap();
+        doc.add_content("image/png", &[0x89, b'P', b'N', b'G'], "a chart").unwrap();
+        doc
+    }
+
+    #[test]
+    fn markdown_uses_sources_and_inlines_images() {
+        let markdown = to_markdown(&document(), true);
+        assert!(markdown.starts_with("# Notes <draft>\n"));
+        assert!(markdown.contains("Some **bold** text"));
+        assert!(markdown.contains("![a chart](data:image/png;base64,"));
+        assert!(!to_markdown(&document(), false).contains("**Author:**"));
+    }
+
+    #[test]
+    fn html_renders_markdown_and_escapes_text() {
+        let html = to_html(&document(), true);
+        assert!(html.contains("<title>Notes &lt;draft&gt;</title>"));
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.contains("<code>code</code>"));
+        assert!(html.contains("Plain &lt;b&gt;not html&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn links_keep_only_safe_targets() {
+        assert_eq!(render_inline("[a](https://x.org/p?q=1)"), "<a href=\"https://x.org/p?q=1\">a</a>");
+        assert_eq!(render_inline("[a](mailto:ada@x.org)"), "<a href=\"mailto:ada@x.org\">a</a>");
+        assert_eq!(render_inline("[a](notes/b.md#part)"), "<a href=\"notes/b.md#part\">a</a>");
+        assert_eq!(render_inline("[a](javascript:void)"), "a");
+        assert_eq!(render_inline("[a](JavaScript:x)"), "a");
+        assert_eq!(render_inline("[a]( data:text/html,x)"), "a");
+    }
+
+    #[test]
+    fn json_export_holds_the_whole_document() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&document()).unwrap()).unwrap();
+        assert_eq!(json["metadata"]["title"], "Notes <draft>");
+        assert_eq!(json["content_items"].as_array().unwrap().len(), 3);
+        assert!(json["graph"]["nodes"].is_array());
+    }
+}
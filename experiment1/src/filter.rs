@@ -0,0 +1,415 @@
+use crate::error::{MadError, MadResult};
+use std::collections::HashMap;
+
+// Filter expressions over string metadata, e.g.
+//   content_type = 'table' AND chapter = 3
+//   language IN ('en', 'de') AND page BETWEEN 10 AND 20
+//   NOT (section_type = 'heading' OR page < 2)
+// Ordering comparisons and BETWEEN are numeric; = and != compare numerically
+// when both sides are numbers and as strings otherwise. Missing keys never match.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetadataFilter {
+    And(Vec<MetadataFilter>),
+    Or(Vec<MetadataFilter>),
+    Not(Box<MetadataFilter>),
+    Compare { key: String, op: CompareOp, value: String },
+    In { key: String, values: Vec<String> },
+    Between { key: String, low: f64, high: f64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(CompareOp),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl MetadataFilter {
+    pub fn parse(expression: &str) -> MadResult<MetadataFilter> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let filter = parser.or_expr()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(filter_error(format!("unexpected {:?}", token))),
+        }
+    }
+
+    pub fn matches(&self, metadata: &HashMap<String, String>) -> bool {
+        match self {
+            MetadataFilter::And(filters) => filters.iter().all(|f| f.matches(metadata)),
+            MetadataFilter::Or(filters) => filters.iter().any(|f| f.matches(metadata)),
+            MetadataFilter::Not(filter) => !filter.matches(metadata),
+            MetadataFilter::Compare { key, op, value } => metadata.get(key)
+                .is_some_and(|actual| compare(actual, *op, value)),
+            MetadataFilter::In { key, values } => metadata.get(key)
+                .is_some_and(|actual| values.iter().any(|value| compare(actual, CompareOp::Eq, value))),
+            MetadataFilter::Between { key, low, high } => metadata.get(key)
+                .and_then(|actual| actual.trim().parse::<f64>().ok())
+                .is_some_and(|actual| *low <= actual && actual <= *high),
+        }
+    }
+}
+
+fn compare(actual: &str, op: CompareOp, expected: &str) -> bool {
+    let numbers = (actual.trim().parse::<f64>(), expected.trim().parse::<f64>());
+    match (op, numbers) {
+        (CompareOp::Eq, (Ok(a), Ok(b))) => a == b,
+        (CompareOp::Ne, (Ok(a), Ok(b))) => a != b,
+        (CompareOp::Eq, _) => actual == expected,
+        (CompareOp::Ne, _) => actual != expected,
+        (CompareOp::Lt, (Ok(a), Ok(b))) => a < b,
+        (CompareOp::Le, (Ok(a), Ok(b))) => a <= b,
+        (CompareOp::Gt, (Ok(a), Ok(b))) => a > b,
+        (CompareOp::Ge, (Ok(a), Ok(b))) => a >= b,
+        _ => false,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> MadResult<Token> {
+        let token = self.tokens.get(self.position).cloned()
+            .ok_or_else(|| filter_error("unexpected end of expression".to_string()))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> MadResult<()> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(filter_error(format!("expected {:?}, found {:?}", expected, token)))
+        }
+    }
+
+    fn or_expr(&mut self) -> MadResult<MetadataFilter> {
+        let mut filters = vec![self.and_expr()?];
+        while self.keyword("OR") {
+            filters.push(self.and_expr()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { MetadataFilter::Or(filters) })
+    }
+
+    fn and_expr(&mut self) -> MadResult<MetadataFilter> {
+        let mut filters = vec![self.unary()?];
+        while self.keyword("AND") {
+            filters.push(self.unary()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { MetadataFilter::And(filters) })
+    }
+
+    fn unary(&mut self) -> MadResult<MetadataFilter> {
+        if self.keyword("NOT") {
+            return Ok(MetadataFilter::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.position += 1;
+            let filter = self.or_expr()?;
+            self.expect(Token::RParen)?;
+            return Ok(filter);
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> MadResult<MetadataFilter> {
+        let key = match self.next()? {
+            Token::Word(word) | Token::Quoted(word) => word,
+            token => return Err(filter_error(format!("expected a metadata key, found {:?}", token))),
+        };
+
+        if self.keyword("IN") {
+            self.expect(Token::LParen)?;
+            let mut values = vec![self.value()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.position += 1;
+                values.push(self.value()?);
+            }
+            self.expect(Token::RParen)?;
+            return Ok(MetadataFilter::In { key, values });
+        }
+
+        if self.keyword("BETWEEN") {
+            let low = self.number()?;
+            if !self.keyword("AND") {
+                return Err(filter_error("BETWEEN needs AND".to_string()));
+            }
+            let high = self.number()?;
+            return Ok(MetadataFilter::Between { key, low, high });
+        }
+
+        let op = match self.next()? {
+            Token::Op(op) => op,
+            token => return Err(filter_error(format!("expected an operator after '{}', found {:?}", key, token))),
+        };
+        let value = self.value()?;
+        if !matches!(op, CompareOp::Eq | CompareOp::Ne) && value.parse::<f64>().is_err() {
+            return Err(filter_error(format!("'{}' needs a numeric value", key)));
+        }
+        Ok(MetadataFilter::Compare { key, op, value })
+    }
+
+    fn value(&mut self) -> MadResult<String> {
+        match self.next()? {
+            Token::Word(word) | Token::Quoted(word) => Ok(word),
+            token => Err(filter_error(format!("expected a value, found {:?}", token))),
+        }
+    }
+
+    fn number(&mut self) -> MadResult<f64> {
+        let value = self.value()?;
+        value.parse().map_err(|_| filter_error(format!("'{}' is not a number", value)))
+    }
+}
+
+fn tokenize(expression: &str) -> MadResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    _ => Token::Comma,
+                });
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(ch) if ch == c => break,
+                        Some(ch) => value.push(ch),
+                        None => return Err(filter_error("unterminated string".to_string())),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let with_eq = chars.peek() == Some(&'=');
+                if with_eq {
+                    chars.next();
+                }
+                let op = match (c, with_eq) {
+                    ('=', _) => CompareOp::Eq,
+                    ('!', true) => CompareOp::Ne,
+                    ('<', false) => CompareOp::Lt,
+                    ('<', true) => CompareOp::Le,
+                    ('>', false) => CompareOp::Gt,
+                    ('>', true) => CompareOp::Ge,
+                    _ => return Err(filter_error("'!' must be followed by '='".to_string())),
+                };
+                tokens.push(Token::Op(op));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || "()=!<>,'\"".contains(ch) {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Conditions on typed graph properties, given as a JSON object. A plain value
+// matches equal values; an object of operators must satisfy each of them:
+//   {"label": "paper", "year": {">=": 2000, "<": 2010}}
+//   {"tags": {"contains": "rust"}, "doi": {"exists": true}}
+// Operators are =, !=, >, >=, <, <=, contains and exists. Ordering compares
+// numbers with numbers and strings with strings; contains matches substrings
+// and list elements. A null value counts as missing, and missing properties
+// only match {"exists": false}.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PropertyFilter {
+    conditions: Vec<(String, PropertyCondition)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyCondition {
+    Compare(CompareOp, serde_json::Value),
+    Contains(serde_json::Value),
+    Exists(bool),
+}
+
+impl PropertyFilter {
+    // An empty string means "no conditions"
+    pub fn parse(filter_json: &str) -> MadResult<PropertyFilter> {
+        if filter_json.trim().is_empty() {
+            return Ok(PropertyFilter::default());
+        }
+        PropertyFilter::from_value(&serde_json::from_str(filter_json)?)
+    }
+
+    pub fn from_value(filter: &serde_json::Value) -> MadResult<PropertyFilter> {
+        let filter = filter.as_object()
+            .ok_or_else(|| property_filter_error("expected an object of property conditions".to_string()))?;
+
+        let mut conditions = Vec::new();
+        for (key, value) in filter {
+            match value {
+                serde_json::Value::Object(operators) => {
+                    if operators.is_empty() {
+                        return Err(property_filter_error(format!("no operators for '{}'", key)));
+                    }
+                    for (operator, operand) in operators {
+                        conditions.push((key.clone(), condition(key, operator, operand)?));
+                    }
+                }
+                value => conditions.push((key.clone(), PropertyCondition::Compare(CompareOp::Eq, value.clone()))),
+            }
+        }
+        Ok(PropertyFilter { conditions })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    pub fn conditions(&self) -> &[(String, PropertyCondition)] {
+        &self.conditions
+    }
+
+    pub fn matches(&self, properties: &HashMap<String, serde_json::Value>) -> bool {
+        self.conditions.iter().all(|(key, condition)| {
+            let actual = properties.get(key).filter(|value| !value.is_null());
+            match (condition, actual) {
+                (PropertyCondition::Exists(exists), actual) => actual.is_some() == *exists,
+                (_, None) => false,
+                (PropertyCondition::Compare(op, expected), Some(actual)) => compare_values(actual, *op, expected),
+                (PropertyCondition::Contains(expected), Some(actual)) => match (actual, expected) {
+                    (serde_json::Value::String(a), serde_json::Value::String(b)) => a.contains(b.as_str()),
+                    (serde_json::Value::Array(items), expected) => items.iter().any(|item| values_equal(item, expected)),
+                    _ => false,
+                },
+            }
+        })
+    }
+}
+
+fn condition(key: &str, operator: &str, operand: &serde_json::Value) -> MadResult<PropertyCondition> {
+    let op = match operator {
+        "=" => CompareOp::Eq,
+        "!=" => CompareOp::Ne,
+        ">" => CompareOp::Gt,
+        ">=" => CompareOp::Ge,
+        "<" => CompareOp::Lt,
+        "<=" => CompareOp::Le,
+        "contains" => return Ok(PropertyCondition::Contains(operand.clone())),
+        "exists" => return operand.as_bool()
+            .map(PropertyCondition::Exists)
+            .ok_or_else(|| property_filter_error(format!("'exists' for '{}' needs true or false", key))),
+        _ => return Err(property_filter_error(format!("unknown operator '{}' for '{}'", operator, key))),
+    };
+    Ok(PropertyCondition::Compare(op, operand.clone()))
+}
+
+fn compare_values(actual: &serde_json::Value, op: CompareOp, expected: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    let ordering = match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().zip(b.as_f64()).and_then(|(a, b)| a.partial_cmp(&b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    match op {
+        CompareOp::Eq => values_equal(actual, expected),
+        CompareOp::Ne => !values_equal(actual, expected),
+        CompareOp::Lt => ordering.is_some_and(|o| o.is_lt()),
+        CompareOp::Le => ordering.is_some_and(|o| o.is_le()),
+        CompareOp::Gt => ordering.is_some_and(|o| o.is_gt()),
+        CompareOp::Ge => ordering.is_some_and(|o| o.is_ge()),
+    }
+}
+
+// Like ==, but 3 and 3.0 are equal
+fn values_equal(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(x, y)| values_equal(x, y)),
+        _ => a == b,
+    }
+}
+
+fn property_filter_error(message: String) -> MadError {
+    MadError::InvalidInput(format!("property filter: {}", message))
+}
+
+fn filter_error(message: String) -> MadError {
+    MadError::InvalidInput(format!("filter: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn matches(expression: &str, pairs: &[(&str, &str)]) -> bool {
+        MetadataFilter::parse(expression).unwrap().matches(&metadata(pairs))
+    }
+
+    #[test]
+    fn parses_and_evaluates_expressions() {
+        let item = [("content_type", "table"), ("chapter", "3"), ("language", "en"), ("page", "12")];
+        assert!(matches("content_type = 'table' AND chapter = 3.0", &item));
+        assert!(matches("language IN ('de', en) AND page BETWEEN 10 AND 20", &item));
+        assert!(matches("NOT (chapter > 3 OR page < 2)", &item));
+        assert!(!matches("missing = 1 OR missing != 1", &item));
+        assert!(!matches("language < 5", &item));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expression in ["", "a =", "a = 1 AND", "(a = 1", "a BETWEEN x AND 2", "a = 1 b"] {
+            assert!(MetadataFilter::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use wasm_bindgen::prelude::*;
+use crate::cypher::{self, CypherResult};
+use crate::cypher_export::{self, CypherStatement};
+use crate::error::{MadError, MadResult};
+use crate::filter::PropertyFilter;
+use crate::graph_index::{LabelIndex, PropertyIndex};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
 
@@ -6,7 +10,7 @@ use std::collections::{HashMap, HashSet, VecDeque};
 pub struct GraphNode {
     pub id: String,
     pub label: String,
-    pub properties: HashMap<String, String>,
+    pub properties: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -15,7 +19,7 @@ pub struct GraphEdge {
     pub from: String,
     pub to: String,
     pub relationship: String,
-    pub properties: HashMap<String, String>,
+    pub properties: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -25,38 +29,125 @@ pub struct GraphPath {
     pub length: usize,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct GraphQueryResult {
     pub nodes: Vec<GraphNode>,
     pub edges: Vec<GraphEdge>,
     pub paths: Vec<GraphPath>,
 }
 
-#[wasm_bindgen]
+// Serialized form of the whole graph, used by the .mad container
+#[derive(Serialize, Deserialize)]
+pub struct GraphSnapshot {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+// Property maps arrive as JSON objects; an empty string means "no properties"
+pub fn parse_properties(properties_json: &str) -> MadResult<HashMap<String, String>> {
+    if properties_json.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+    Ok(serde_json::from_str(properties_json)?)
+}
+
+// Graph properties are typed: strings, numbers, booleans, lists or null.
+// Nested objects, mixed-type lists and lists holding null are rejected, as
+// Neo4j and Memgraph can't store them either. Loaded snapshots only get
+// check_property_values, so documents saved before lists were checked still
+// open; cypher_export refuses to write them out.
+pub fn parse_property_values(properties_json: &str) -> MadResult<HashMap<String, serde_json::Value>> {
+    if properties_json.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+    let properties: HashMap<String, serde_json::Value> = serde_json::from_str(properties_json)?;
+    check_property_values(&properties)?;
+    check_list_values(&properties)?;
+    Ok(properties)
+}
+
+pub fn check_property_values(properties: &HashMap<String, serde_json::Value>) -> MadResult<()> {
+    // Cypher exports keep node and edge IDs under this key
+    if properties.contains_key(cypher_export::ID_PROPERTY) {
+        return Err(MadError::InvalidInput(format!(
+            "Property '{}' is reserved for node and edge IDs",
+            cypher_export::ID_PROPERTY
+        )));
+    }
+
+    fn has_object(value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Object(_) => true,
+            serde_json::Value::Array(items) => items.iter().any(has_object),
+            _ => false,
+        }
+    }
+
+    match properties.iter().find(|(_, value)| has_object(value)) {
+        Some((key, _)) => Err(MadError::InvalidInput(format!(
+            "Property '{}' contains an object; values must be strings, numbers, booleans, lists or null",
+            key
+        ))),
+        None => Ok(()),
+    }
+}
+
+// Cypher targets only store lists holding a single scalar type
+pub fn check_list_values(properties: &HashMap<String, serde_json::Value>) -> MadResult<()> {
+    fn kind(value: &serde_json::Value) -> Option<&'static str> {
+        match value {
+            serde_json::Value::String(_) => Some("string"),
+            serde_json::Value::Number(_) => Some("number"),
+            serde_json::Value::Bool(_) => Some("boolean"),
+            _ => None,
+        }
+    }
+
+    for (key, value) in properties {
+        if let serde_json::Value::Array(items) = value {
+            let first = items.first().and_then(kind);
+            if items.iter().any(|item| kind(item).is_none() || kind(item) != first) {
+                return Err(MadError::InvalidInput(format!(
+                    "Property '{}' must be a list of only strings, only numbers or only booleans",
+                    key
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
 pub struct GraphDatabase {
     nodes: HashMap<String, GraphNode>,
     edges: HashMap<String, GraphEdge>,
     // Adjacency list for efficient traversal
     outgoing: HashMap<String, Vec<String>>, // node_id -> edge_ids
     incoming: HashMap<String, Vec<String>>, // node_id -> edge_ids
+    // Secondary indexes for label and property lookups
+    labels: LabelIndex,
+    node_properties: PropertyIndex,
+    edge_properties: PropertyIndex,
 }
 
-#[wasm_bindgen]
 impl GraphDatabase {
-    #[wasm_bindgen(constructor)]
     pub fn new() -> GraphDatabase {
         GraphDatabase {
             nodes: HashMap::new(),
             edges: HashMap::new(),
             outgoing: HashMap::new(),
             incoming: HashMap::new(),
+            labels: LabelIndex::default(),
+            node_properties: PropertyIndex::default(),
+            edge_properties: PropertyIndex::default(),
         }
     }
 
-    #[wasm_bindgen]
-    pub fn add_node(&mut self, id: &str, label: &str, properties_json: &str) -> Result<(), JsValue> {
-        let properties: HashMap<String, String> = serde_json::from_str(properties_json)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    pub fn add_node(&mut self, id: &str, label: &str, properties_json: &str) -> MadResult<()> {
+        let properties = parse_property_values(properties_json)?;
+
+        if self.nodes.contains_key(id) {
+            return Err(MadError::AlreadyExists(format!("Node {}", id)));
+        }
 
         let node = GraphNode {
             id: id.to_string(),
@@ -64,21 +155,36 @@ impl GraphDatabase {
             properties,
         };
 
-        self.nodes.insert(id.to_string(), node);
-        self.outgoing.entry(id.to_string()).or_insert_with(Vec::new);
-        self.incoming.entry(id.to_string()).or_insert_with(Vec::new);
-
+        self.insert_node(node);
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn add_edge(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> Result<String, JsValue> {
-        let properties: HashMap<String, String> = serde_json::from_str(properties_json)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    fn insert_node(&mut self, node: GraphNode) {
+        if let Some(old) = self.nodes.remove(&node.id) {
+            self.labels.remove(&old.label, &old.id);
+            self.node_properties.remove(&old.id, &old.properties);
+        }
+        self.labels.insert(&node.label, &node.id);
+        self.node_properties.insert(&node.id, &node.properties);
+        self.outgoing.entry(node.id.clone()).or_default();
+        self.incoming.entry(node.id.clone()).or_default();
+        self.nodes.insert(node.id.clone(), node);
+    }
 
+    pub fn add_edge(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> MadResult<String> {
         let edge_id = uuid::Uuid::new_v4().to_string();
+        self.add_edge_with_id(&edge_id, from, to, relationship, properties_json)
+    }
+
+    pub fn add_edge_with_id(&mut self, edge_id: &str, from: &str, to: &str, relationship: &str, properties_json: &str) -> MadResult<String> {
+        let properties = parse_property_values(properties_json)?;
+
+        if self.edges.contains_key(edge_id) {
+            return Err(MadError::AlreadyExists(format!("Edge {}", edge_id)));
+        }
+
         let edge = GraphEdge {
-            id: edge_id.clone(),
+            id: edge_id.to_string(),
             from: from.to_string(),
             to: to.to_string(),
             relationship: relationship.to_string(),
@@ -87,32 +193,109 @@ impl GraphDatabase {
 
         // Ensure nodes exist
         if !self.nodes.contains_key(from) || !self.nodes.contains_key(to) {
-            return Err(JsValue::from_str("Source or target node does not exist"));
+            return Err(MadError::NotFound("Source or target node".to_string()));
         }
 
-        self.edges.insert(edge_id.clone(), edge);
-        
+        self.insert_edge(edge);
+        Ok(edge_id.to_string())
+    }
+
+    fn insert_edge(&mut self, edge: GraphEdge) {
         // Update adjacency lists
-        self.outgoing.entry(from.to_string()).or_insert_with(Vec::new).push(edge_id.clone());
-        self.incoming.entry(to.to_string()).or_insert_with(Vec::new).push(edge_id.clone());
+        self.outgoing.entry(edge.from.clone()).or_default().push(edge.id.clone());
+        self.incoming.entry(edge.to.clone()).or_default().push(edge.id.clone());
+        self.edge_properties.insert(&edge.id, &edge.properties);
+        self.edges.insert(edge.id.clone(), edge);
+    }
 
-        Ok(edge_id)
+    // Creates the node, or updates the label and merges the properties of an
+    // existing one. Returns true when the node was created.
+    pub fn merge_node(&mut self, id: &str, label: &str, properties_json: &str) -> MadResult<bool> {
+        if !self.nodes.contains_key(id) {
+            self.add_node(id, label, properties_json)?;
+            return Ok(true);
+        }
+        self.update_node_properties(id, properties_json)?;
+        if let Some(node) = self.nodes.get_mut(id) {
+            self.labels.remove(&node.label, id);
+            self.labels.insert(label, id);
+            node.label = label.to_string();
+        }
+        Ok(false)
+    }
+
+    // Sets the given properties and leaves the others alone; a null value
+    // removes the property
+    pub fn update_node_properties(&mut self, id: &str, properties_json: &str) -> MadResult<()> {
+        let updates = parse_property_values(properties_json)?;
+        let node = self.nodes.get_mut(id)
+            .ok_or_else(|| MadError::NotFound(format!("Node {}", id)))?;
+
+        self.node_properties.remove(id, &node.properties);
+        for (key, value) in updates {
+            if value.is_null() {
+                node.properties.remove(&key);
+            } else {
+                node.properties.insert(key, value);
+            }
+        }
+        self.node_properties.insert(id, &node.properties);
+        Ok(())
+    }
+
+    // Removes the node and every edge to or from it. Returns the number of
+    // edges removed.
+    pub fn remove_node(&mut self, id: &str) -> MadResult<usize> {
+        if !self.nodes.contains_key(id) {
+            return Err(MadError::NotFound(format!("Node {}", id)));
+        }
+
+        let mut edge_ids: Vec<String> = self.outgoing.remove(id).unwrap_or_default();
+        edge_ids.extend(self.incoming.remove(id).unwrap_or_default());
+        // Self-loops are listed on both sides
+        edge_ids.sort();
+        edge_ids.dedup();
+        for edge_id in &edge_ids {
+            self.detach_edge(edge_id);
+        }
+
+        if let Some(node) = self.nodes.remove(id) {
+            self.labels.remove(&node.label, id);
+            self.node_properties.remove(id, &node.properties);
+        }
+        Ok(edge_ids.len())
+    }
+
+    pub fn remove_edge(&mut self, edge_id: &str) -> MadResult<()> {
+        self.detach_edge(edge_id)
+            .map(|_| ())
+            .ok_or_else(|| MadError::NotFound(format!("Edge {}", edge_id)))
+    }
+
+    fn detach_edge(&mut self, edge_id: &str) -> Option<GraphEdge> {
+        let edge = self.edges.remove(edge_id)?;
+        self.edge_properties.remove(edge_id, &edge.properties);
+        if let Some(ids) = self.outgoing.get_mut(&edge.from) {
+            ids.retain(|id| id != edge_id);
+        }
+        if let Some(ids) = self.incoming.get_mut(&edge.to) {
+            ids.retain(|id| id != edge_id);
+        }
+        Some(edge)
     }
 
-    #[wasm_bindgen]
     pub fn get_node(&self, id: &str) -> Option<String> {
         self.nodes.get(id).and_then(|node| serde_json::to_string(node).ok())
     }
 
-    #[wasm_bindgen]
-    pub fn get_neighbors(&self, node_id: &str, relationship_filter: Option<String>) -> String {
+    pub fn get_neighbors(&self, node_id: &str, relationship_filter: Option<&str>) -> String {
         let mut neighbors = Vec::new();
 
         if let Some(edge_ids) = self.outgoing.get(node_id) {
             for edge_id in edge_ids {
                 if let Some(edge) = self.edges.get(edge_id) {
-                    if let Some(ref filter) = relationship_filter {
-                        if edge.relationship != *filter {
+                    if let Some(filter) = relationship_filter {
+                        if edge.relationship != filter {
                             continue;
                         }
                     }
@@ -126,13 +309,12 @@ impl GraphDatabase {
         serde_json::to_string(&neighbors).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
-    pub fn traverse(&self, start_node: &str, max_depth: usize, relationship_filter: Option<String>) -> String {
-        let mut result = GraphQueryResult {
-            nodes: Vec::new(),
-            edges: Vec::new(),
-            paths: Vec::new(),
-        };
+    pub fn traverse(&self, start_node: &str, max_depth: usize, relationship_filter: Option<&str>) -> String {
+        serde_json::to_string(&self.traverse_result(start_node, max_depth, relationship_filter)).unwrap_or_default()
+    }
+
+    pub fn traverse_result(&self, start_node: &str, max_depth: usize, relationship_filter: Option<&str>) -> GraphQueryResult {
+        let mut result = GraphQueryResult::default();
 
         let mut visited_nodes = HashSet::new();
         let mut visited_edges = HashSet::new();
@@ -143,16 +325,17 @@ impl GraphDatabase {
         visited_nodes.insert(start_node.to_string());
 
         while let Some((current_node, depth)) = queue.pop_front() {
-            if depth >= max_depth {
-                continue;
-            }
-
+            // Nodes at the depth limit are included, but not expanded further
             if let Some(node) = self.nodes.get(&current_node) {
                 if !result.nodes.iter().any(|n| n.id == node.id) {
                     result.nodes.push(node.clone());
                 }
             }
 
+            if depth >= max_depth {
+                continue;
+            }
+
             if let Some(edge_ids) = self.outgoing.get(&current_node) {
                 for edge_id in edge_ids {
                     if visited_edges.contains(edge_id) {
@@ -161,8 +344,8 @@ impl GraphDatabase {
 
                     if let Some(edge) = self.edges.get(edge_id) {
                         // Apply relationship filter
-                        if let Some(ref filter) = relationship_filter {
-                            if edge.relationship != *filter {
+                        if let Some(filter) = relationship_filter {
+                            if edge.relationship != filter {
                                 continue;
                             }
                         }
@@ -179,11 +362,14 @@ impl GraphDatabase {
             }
         }
 
-        serde_json::to_string(&result).unwrap_or_default()
+        result
     }
 
-    #[wasm_bindgen]
-    pub fn find_shortest_path(&self, start: &str, end: &str, relationship_filter: Option<String>) -> String {
+    pub fn find_shortest_path(&self, start: &str, end: &str, relationship_filter: Option<&str>) -> String {
+        serde_json::to_string(&self.shortest_path_result(start, end, relationship_filter)).unwrap_or_default()
+    }
+
+    pub fn shortest_path_result(&self, start: &str, end: &str, relationship_filter: Option<&str>) -> GraphQueryResult {
         let mut queue = VecDeque::new();
         let mut visited = HashSet::new();
         let mut parent: HashMap<String, String> = HashMap::new();
@@ -225,21 +411,19 @@ impl GraphDatabase {
                     length: path_edges.len(),
                 };
 
-                let result = GraphQueryResult {
+                return GraphQueryResult {
                     nodes: path_nodes,
                     edges: path_edges,
                     paths: vec![path],
                 };
-
-                return serde_json::to_string(&result).unwrap_or_default();
             }
 
             if let Some(edge_ids) = self.outgoing.get(&current) {
                 for edge_id in edge_ids {
                     if let Some(edge) = self.edges.get(edge_id) {
                         // Apply relationship filter
-                        if let Some(ref filter) = relationship_filter {
-                            if edge.relationship != *filter {
+                        if let Some(filter) = relationship_filter {
+                            if edge.relationship != filter {
                                 continue;
                             }
                         }
@@ -256,63 +440,47 @@ impl GraphDatabase {
         }
 
         // No path found
-        let result = GraphQueryResult {
-            nodes: Vec::new(),
-            edges: Vec::new(),
-            paths: Vec::new(),
-        };
+        GraphQueryResult::default()
+    }
+
+    // Filters are JSON objects of property conditions; see PropertyFilter
+    pub fn query_by_properties(&self, node_properties: &str, edge_properties: &str) -> MadResult<String> {
+        let node_filter = PropertyFilter::parse(node_properties)?;
+        let edge_filter = PropertyFilter::parse(edge_properties)?;
 
-        serde_json::to_string(&result).unwrap_or_default()
+        Ok(serde_json::to_string(&self.properties_query_result(&node_filter, &edge_filter))?)
     }
 
-    #[wasm_bindgen]
-    pub fn query_by_properties(&self, node_properties: &str, edge_properties: &str) -> String {
-        let node_filters: HashMap<String, String> = serde_json::from_str(node_properties)
-            .unwrap_or_default();
-        let edge_filters: HashMap<String, String> = serde_json::from_str(edge_properties)
-            .unwrap_or_default();
+    // Uses the property indexes to narrow the search; results are in ID order
+    pub fn properties_query_result(&self, node_filter: &PropertyFilter, edge_filter: &PropertyFilter) -> GraphQueryResult {
+        let mut nodes: Vec<GraphNode> = match self.node_properties.candidates(node_filter) {
+            Some(ids) => ids.into_iter().filter_map(|id| self.nodes.get(id)).filter(|node| node_filter.matches(&node.properties)).cloned().collect(),
+            None => self.nodes.values().filter(|node| node_filter.matches(&node.properties)).cloned().collect(),
+        };
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
 
-        let mut matching_nodes = Vec::new();
-        let mut matching_edges = Vec::new();
+        let mut edges: Vec<GraphEdge> = match self.edge_properties.candidates(edge_filter) {
+            Some(ids) => ids.into_iter().filter_map(|id| self.edges.get(id)).filter(|edge| edge_filter.matches(&edge.properties)).cloned().collect(),
+            None => self.edges.values().filter(|edge| edge_filter.matches(&edge.properties)).cloned().collect(),
+        };
+        edges.sort_by(|a, b| a.id.cmp(&b.id));
 
-        // Filter nodes by properties
-        for node in self.nodes.values() {
-            let mut matches = true;
-            for (key, value) in &node_filters {
-                if node.properties.get(key) != Some(value) {
-                    matches = false;
-                    break;
-                }
-            }
-            if matches {
-                matching_nodes.push(node.clone());
-            }
-        }
+        GraphQueryResult { nodes, edges, paths: Vec::new() }
+    }
 
-        // Filter edges by properties
-        for edge in self.edges.values() {
-            let mut matches = true;
-            for (key, value) in &edge_filters {
-                if edge.properties.get(key) != Some(value) {
-                    matches = false;
-                    break;
-                }
-            }
-            if matches {
-                matching_edges.push(edge.clone());
-            }
-        }
+    // Nodes with this label, in ID order
+    pub fn nodes_by_label<'a>(&'a self, label: &str) -> impl Iterator<Item = &'a GraphNode> + 'a {
+        self.labels.ids(label).filter_map(|id| self.nodes.get(id))
+    }
 
-        let result = GraphQueryResult {
-            nodes: matching_nodes,
-            edges: matching_edges,
-            paths: Vec::new(),
-        };
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
 
-        serde_json::to_string(&result).unwrap_or_default()
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
     }
 
-    #[wasm_bindgen]
     pub fn get_statistics(&self) -> String {
         let stats = serde_json::json!({
             "node_count": self.nodes.len(),
@@ -326,11 +494,9 @@ impl GraphDatabase {
     }
 
     fn get_unique_labels(&self) -> Vec<String> {
-        let mut labels: HashSet<String> = HashSet::new();
-        for node in self.nodes.values() {
-            labels.insert(node.label.clone());
-        }
-        labels.into_iter().collect()
+        let mut labels: Vec<String> = self.labels.labels().map(str::to_string).collect();
+        labels.sort();
+        labels
     }
 
     fn get_unique_relationships(&self) -> Vec<String> {
@@ -353,46 +519,233 @@ impl GraphDatabase {
         total_degree as f64 / self.nodes.len() as f64
     }
 
-    #[wasm_bindgen]
-    pub fn export_cypher(&self) -> String {
-        let mut cypher_statements = Vec::new();
+    pub fn export_json(&self) -> String {
+        serde_json::to_string(&self.snapshot()).unwrap_or_default()
+    }
 
-        // Create nodes
-        for node in self.nodes.values() {
-            let props: Vec<String> = node.properties.iter()
-                .map(|(k, v)| format!("{}: '{}'", k, v.replace("'", "\\'")))
-                .collect();
-            
-            let props_str = if props.is_empty() {
-                String::new()
-            } else {
-                format!(" {{{}}}", props.join(", "))
-            };
+    pub fn snapshot(&self) -> GraphSnapshot {
+        // Sort by ID so the same graph always serializes to the same bytes
+        let mut nodes: Vec<GraphNode> = self.nodes.values().cloned().collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut edges: Vec<GraphEdge> = self.edges.values().cloned().collect();
+        edges.sort_by(|a, b| a.id.cmp(&b.id));
 
-            cypher_statements.push(format!(
-                "CREATE ({}:{}{})",
-                node.id, node.label, props_str
-            ));
-        }
+        GraphSnapshot { nodes, edges }
+    }
 
-        // Create relationships
-        for edge in self.edges.values() {
-            let props: Vec<String> = edge.properties.iter()
-                .map(|(k, v)| format!("{}: '{}'", k, v.replace("'", "\\'")))
-                .collect();
-            
-            let props_str = if props.is_empty() {
-                String::new()
-            } else {
-                format!(" {{{}}}", props.join(", "))
-            };
+    pub fn import_json(graph_json: &str) -> MadResult<GraphDatabase> {
+        let snapshot: GraphSnapshot = serde_json::from_str(graph_json)?;
+        GraphDatabase::from_snapshot(snapshot)
+    }
 
-            cypher_statements.push(format!(
-                "MATCH (a), (b) WHERE a.id = '{}' AND b.id = '{}' CREATE (a)-[:{}{}]->(b)",
-                edge.from, edge.to, edge.relationship, props_str
-            ));
+    pub fn from_snapshot(snapshot: GraphSnapshot) -> MadResult<GraphDatabase> {
+        let mut graph = GraphDatabase::new();
+        for node in snapshot.nodes {
+            check_property_values(&node.properties)?;
+            if graph.nodes.contains_key(&node.id) {
+                return Err(MadError::AlreadyExists(format!("Node {}", node.id)));
+            }
+            graph.insert_node(node);
+        }
+
+        for edge in snapshot.edges {
+            if !graph.nodes.contains_key(&edge.from) || !graph.nodes.contains_key(&edge.to) {
+                return Err(MadError::NotFound("Source or target node".to_string()));
+            }
+            check_property_values(&edge.properties)?;
+            if graph.edges.contains_key(&edge.id) {
+                return Err(MadError::AlreadyExists(format!("Edge {}", edge.id)));
+            }
+            graph.insert_edge(edge);
         }
 
-        cypher_statements.join(";\n") + ";"
+        Ok(graph)
+    }
+
+    // Typed accessors for native callers
+    pub fn node(&self, id: &str) -> Option<&GraphNode> {
+        self.nodes.get(id)
+    }
+
+    pub fn edge(&self, id: &str) -> Option<&GraphEdge> {
+        self.edges.get(id)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &GraphNode> {
+        self.nodes.values()
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = &GraphEdge> {
+        self.edges.values()
+    }
+
+    // Edges leaving / entering a node, in insertion order
+    pub fn outgoing_edges(&self, node_id: &str) -> impl Iterator<Item = &GraphEdge> {
+        self.outgoing.get(node_id).into_iter().flatten().filter_map(|id| self.edges.get(id))
     }
-}
\ No newline at end of file
+
+    pub fn incoming_edges(&self, node_id: &str) -> impl Iterator<Item = &GraphEdge> {
+        self.incoming.get(node_id).into_iter().flatten().filter_map(|id| self.edges.get(id))
+    }
+
+    // Read-only Cypher subset; see cypher.rs for what is supported
+    pub fn query_cypher(&self, query: &str) -> MadResult<String> {
+        Ok(serde_json::to_string(&self.cypher_result(query)?)?)
+    }
+
+    pub fn cypher_result(&self, query: &str) -> MadResult<CypherResult> {
+        cypher::execute(self, query)
+    }
+
+    // Cypher scripts for Neo4j / Memgraph; see cypher_export.rs
+    pub fn export_cypher(&self) -> MadResult<String> {
+        cypher_export::export_statements(&self.snapshot())
+    }
+
+    pub fn export_cypher_batched(&self, batch_size: usize) -> MadResult<String> {
+        cypher_export::export_batched(&self.snapshot(), batch_size)
+    }
+
+    pub fn cypher_batches(&self, batch_size: usize) -> MadResult<Vec<CypherStatement>> {
+        cypher_export::batches(&self.snapshot(), batch_size)
+    }
+
+    pub fn import_cypher(script: &str) -> MadResult<GraphDatabase> {
+        GraphDatabase::from_snapshot(cypher_export::import(script)?)
+    }
+}
+
+impl Default for GraphDatabase {
+    fn default() -> Self {
+        GraphDatabase::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> GraphDatabase {
+        let mut graph = GraphDatabase::new();
+        graph.add_node("alice", "Person", r#"{"name": "Alice", "age": 34, "tags": ["rust"]}"#).unwrap();
+        graph.add_node("bob", "Person", r#"{"name": "Bob", "age": 27}"#).unwrap();
+        graph.add_node("doc", "Document", r#"{"title": "Notes"}"#).unwrap();
+        graph.add_edge_with_id("e1", "alice", "bob", "KNOWS", r#"{"since": 2019}"#).unwrap();
+        graph.add_edge_with_id("e2", "alice", "doc", "WROTE", "").unwrap();
+        graph
+    }
+
+    fn ids(result: &GraphQueryResult) -> Vec<&str> {
+        result.nodes.iter().map(|node| node.id.as_str()).collect()
+    }
+
+    fn query(graph: &GraphDatabase, filter: &str) -> Vec<String> {
+        let filter = PropertyFilter::parse(filter).unwrap();
+        ids(&graph.properties_query_result(&filter, &PropertyFilter::default())).into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn rejects_duplicates_and_dangling_edges() {
+        let mut graph = sample();
+        assert!(matches!(graph.add_node("bob", "Person", ""), Err(MadError::AlreadyExists(_))));
+        assert!(matches!(graph.add_edge("bob", "carol", "KNOWS", ""), Err(MadError::NotFound(_))));
+        assert!(matches!(graph.add_edge_with_id("e1", "bob", "doc", "READ", ""), Err(MadError::AlreadyExists(_))));
+        assert!(matches!(graph.add_node("x", "N", "{not json"), Err(MadError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn snapshots_reject_duplicate_ids() {
+        let node = r#"{"id": "a", "label": "N", "properties": {}}"#;
+        let nodes = format!(r#"{{"nodes": [{}, {}], "edges": []}}"#, node, node);
+        assert!(matches!(GraphDatabase::import_json(&nodes), Err(MadError::AlreadyExists(_))));
+
+        let edge = r#"{"id": "e", "from": "a", "to": "a", "relationship": "R", "properties": {}}"#;
+        let edges = format!(r#"{{"nodes": [{}], "edges": [{}, {}]}}"#, node, edge, edge);
+        assert!(matches!(GraphDatabase::import_json(&edges), Err(MadError::AlreadyExists(_))));
+    }
+
+    #[test]
+    fn traverses_and_finds_paths() {
+        let graph = sample();
+        let result = graph.traverse_result("alice", 1, Some("KNOWS"));
+        assert_eq!(ids(&result), vec!["alice", "bob"]);
+
+        let path = graph.shortest_path_result("bob", "doc", None);
+        assert!(path.paths.is_empty());
+        let path = graph.shortest_path_result("alice", "doc", None);
+        assert_eq!(path.paths[0].length, 1);
+    }
+
+    #[test]
+    fn json_snapshot_round_trips() {
+        let graph = sample();
+        let loaded = GraphDatabase::import_json(&graph.export_json()).unwrap();
+        assert_eq!(serde_json::to_value(loaded.snapshot()).unwrap(), serde_json::to_value(graph.snapshot()).unwrap());
+        assert_eq!(loaded.nodes_by_label("Person").count(), 2);
+        assert_eq!(loaded.outgoing_edges("alice").count(), 2);
+    }
+
+    #[test]
+    fn property_queries_support_operators() {
+        let graph = sample();
+        assert_eq!(query(&graph, r#"{"age": {">": 30}}"#), vec!["alice"]);
+        assert_eq!(query(&graph, r#"{"age": {">=": 27, "<": 34}}"#), vec!["bob"]);
+        assert_eq!(query(&graph, r#"{"age": 34.0}"#), vec!["alice"]);
+        assert_eq!(query(&graph, r#"{"age": "34"}"#), Vec::<String>::new());
+        assert_eq!(query(&graph, r#"{"tags": {"contains": "rust"}}"#), vec!["alice"]);
+        assert_eq!(query(&graph, r#"{"age": {"exists": false}}"#), vec!["doc"]);
+        assert!(PropertyFilter::parse(r#"{"age": {"~": 1}}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_object_values() {
+        let mut graph = sample();
+        assert!(matches!(graph.add_node("x", "N", r#"{"nested": {"a": 1}}"#), Err(MadError::InvalidInput(_))));
+        assert!(matches!(graph.add_node("x", "N", r#"{"list": [{"a": 1}]}"#), Err(MadError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn rejects_mixed_and_null_holding_lists() {
+        let mut graph = sample();
+        assert!(matches!(graph.add_node("x", "N", r#"{"list": [1, "a"]}"#), Err(MadError::InvalidInput(_))));
+        assert!(matches!(graph.add_node("x", "N", r#"{"list": ["a", null]}"#), Err(MadError::InvalidInput(_))));
+        assert!(matches!(graph.add_node("x", "N", r#"{"list": [[1], [2]]}"#), Err(MadError::InvalidInput(_))));
+        assert!(matches!(graph.update_node_properties("bob", r#"{"tags": [true, 1]}"#), Err(MadError::InvalidInput(_))));
+        graph.add_node("x", "N", r#"{"list": [1, 2.5], "empty": [], "gone": null}"#).unwrap();
+    }
+
+    #[test]
+    fn updates_merge_and_remove_keep_indexes_current() {
+        let mut graph = sample();
+        graph.update_node_properties("bob", r#"{"age": 28, "name": null}"#).unwrap();
+        assert_eq!(graph.node("bob").unwrap().properties.get("age"), Some(&serde_json::json!(28)));
+        assert!(!graph.node("bob").unwrap().properties.contains_key("name"));
+        assert_eq!(query(&graph, r#"{"age": 27}"#), Vec::<String>::new());
+        assert_eq!(query(&graph, r#"{"name": {"exists": true}}"#), vec!["alice"]);
+
+        assert!(!graph.merge_node("bob", "Author", r#"{"name": "Robert"}"#).unwrap());
+        assert!(graph.merge_node("carol", "Person", "").unwrap());
+        assert_eq!(query(&graph, r#"{"name": "Robert"}"#), vec!["bob"]);
+
+        assert_eq!(graph.remove_node("alice").unwrap(), 2);
+        assert_eq!(graph.edge_count(), 0);
+        assert!(graph.outgoing_edges("alice").next().is_none());
+        assert!(graph.incoming_edges("bob").next().is_none());
+        assert!(query(&graph, r#"{"tags": {"contains": "rust"}}"#).is_empty());
+        assert!(matches!(graph.remove_edge("e1"), Err(MadError::NotFound(_))));
+    }
+
+    #[test]
+    fn label_index_follows_merges_and_removals() {
+        let mut graph = sample();
+        graph.merge_node("bob", "Author", "").unwrap();
+        graph.merge_node("carol", "Person", "").unwrap();
+        let people: Vec<&str> = graph.nodes_by_label("Person").map(|node| node.id.as_str()).collect();
+        assert_eq!(people, vec!["alice", "carol"]);
+
+        graph.remove_node("alice").unwrap();
+        let people: Vec<&str> = graph.nodes_by_label("Person").map(|node| node.id.as_str()).collect();
+        assert_eq!(people, vec!["carol"]);
+        assert_eq!(graph.nodes_by_label("Author").count(), 1);
+    }
+}
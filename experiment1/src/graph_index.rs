@@ -0,0 +1,104 @@
+use crate::filter::{CompareOp, PropertyCondition, PropertyFilter};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// Secondary indexes kept up to date by GraphDatabase on every insert, update
+// and removal. They only narrow down candidates; callers still check each
+// candidate against the full filter.
+
+// Label -> node IDs, in ID order
+#[derive(Default)]
+pub struct LabelIndex {
+    ids: HashMap<String, BTreeSet<String>>,
+}
+
+impl LabelIndex {
+    pub fn insert(&mut self, label: &str, id: &str) {
+        self.ids.entry(label.to_string()).or_default().insert(id.to_string());
+    }
+
+    pub fn remove(&mut self, label: &str, id: &str) {
+        if let Some(ids) = self.ids.get_mut(label) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.ids.remove(label);
+            }
+        }
+    }
+
+    pub fn ids(&self, label: &str) -> impl Iterator<Item = &str> {
+        self.ids.get(label).into_iter().flatten().map(String::as_str)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.ids.keys().map(String::as_str)
+    }
+}
+
+// Property key -> IDs with a non-null value, and key -> value -> IDs for
+// equality lookups
+#[derive(Default)]
+pub struct PropertyIndex {
+    keys: HashMap<String, HashSet<String>>,
+    values: HashMap<String, HashMap<String, HashSet<String>>>,
+}
+
+impl PropertyIndex {
+    pub fn insert(&mut self, id: &str, properties: &HashMap<String, serde_json::Value>) {
+        for (key, value) in properties.iter().filter(|(_, value)| !value.is_null()) {
+            self.keys.entry(key.clone()).or_default().insert(id.to_string());
+            self.values.entry(key.clone()).or_default()
+                .entry(value_key(value)).or_default()
+                .insert(id.to_string());
+        }
+    }
+
+    pub fn remove(&mut self, id: &str, properties: &HashMap<String, serde_json::Value>) {
+        for (key, value) in properties.iter().filter(|(_, value)| !value.is_null()) {
+            if let Some(ids) = self.keys.get_mut(key) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.keys.remove(key);
+                }
+            }
+            if let Some(values) = self.values.get_mut(key) {
+                let value = value_key(value);
+                if let Some(ids) = values.get_mut(&value) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        values.remove(&value);
+                    }
+                }
+                if values.is_empty() {
+                    self.values.remove(key);
+                }
+            }
+        }
+    }
+
+    // IDs from the most selective condition the index can answer, or None
+    // when no condition can use it (an empty filter, or only
+    // {"exists": false} conditions)
+    pub fn candidates(&self, filter: &PropertyFilter) -> Option<Vec<&str>> {
+        let smallest = filter.conditions().iter()
+            .filter_map(|(key, condition)| match condition {
+                PropertyCondition::Exists(false) => None,
+                PropertyCondition::Compare(CompareOp::Eq, value) => Some(
+                    self.values.get(key).and_then(|values| values.get(&value_key(value)))
+                ),
+                _ => Some(self.keys.get(key)),
+            })
+            .min_by_key(|ids| ids.map_or(0, HashSet::len))?;
+
+        Some(smallest.into_iter().flatten().map(String::as_str).collect())
+    }
+}
+
+// Values that PropertyFilter treats as equal share a key: numbers are compared
+// as f64, so 3 and 3.0 are the same
+fn value_key(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64().map(|n| n.to_string()).unwrap_or_else(|| n.to_string()),
+        serde_json::Value::Array(items) => format!("[{}]", items.iter().map(value_key).collect::<Vec<_>>().join(",")),
+        other => other.to_string(),
+    }
+}
@@ -1,34 +1,74 @@
-use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
 pub mod mcp_server;
 pub mod graph_db;
+pub mod graph_index;
 pub mod builder;
 pub mod sharing;
-pub use mcp_server::McpServer;
+pub mod container;
+pub mod export;
+pub mod adam;
+pub mod vector_index;
+pub mod text_index;
+pub mod search;
+pub mod filter;
+pub mod cypher;
+pub mod cypher_export;
+pub mod chunker;
+pub mod embedding;
+pub mod quantization;
+pub mod pagination;
+pub mod clock;
+pub mod error;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub use mcp_server::{McpLimits, McpServer};
 pub use graph_db::GraphDatabase;
 pub use builder::MadBuilder;
 pub use sharing::SharingManager;
-
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
-
-macro_rules! console_log {
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
-}
+pub use container::MadContainer;
+pub use vector_index::{HnswIndex, HnswParams};
+pub use text_index::{TextIndex, TextSearchHit, TextSpan};
+pub use search::{FusionMethod, HybridSearchHit, HybridSearchOptions, SearchFilter, VectorMatch, VectorSearchHit, VectorSearchOptions};
+pub use filter::{MetadataFilter, PropertyFilter};
+pub use cypher::CypherResult;
+pub use cypher_export::CypherStatement;
+pub use chunker::{Chunk, ChunkOptions, ChunkStrategy};
+pub use embedding::{EmbeddingProvider, HashedEmbeddingProvider};
+pub use quantization::{Quantization, QuantizedVector};
+pub use pagination::Page;
+#[cfg(feature = "http-embeddings")]
+pub use embedding::HttpEmbeddingProvider;
+pub use clock::{Clock, FixedClock, SharedClock, SystemClock};
+pub use error::{MadError, MadResult};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DocumentMetadata {
     pub id: String,
     pub title: String,
     pub author: String,
+    // Individual names when there are several; `author` holds them joined
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
     pub created: u64,
+    // Last modification time recorded by an imported document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
     pub content_hash: String,
     pub version: String,
+    // Set when embeddings come from an EmbeddingProvider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_dimension: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector_quantization: Option<Quantization>,
+    // Whether quantized documents also store full-precision vectors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_full_precision: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -44,15 +84,43 @@ pub struct ContentItem {
 pub struct VectorEmbedding {
     pub id: String,
     pub content: String,
+    // Empty when only the quantized form is stored
     pub embedding: Vec<f32>,
     pub metadata: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantized: Option<QuantizedVector>,
+}
+
+impl VectorEmbedding {
+    pub fn dimension(&self) -> usize {
+        match &self.quantized {
+            Some(quantized) if self.embedding.is_empty() => quantized.dimension,
+            _ => self.embedding.len(),
+        }
+    }
+
+    // What search runs on: the quantized form when there is one
+    pub fn search_vector(&self) -> Cow<'_, [f32]> {
+        match &self.quantized {
+            Some(quantized) => Cow::Owned(quantized.decode()),
+            None => Cow::Borrowed(&self.embedding),
+        }
+    }
+
+    // Full precision when kept, otherwise the quantized approximation
+    pub fn full_vector(&self) -> Cow<'_, [f32]> {
+        match &self.quantized {
+            Some(quantized) if self.embedding.is_empty() => Cow::Owned(quantized.decode()),
+            _ => Cow::Borrowed(&self.embedding),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct GraphNode {
     pub id: String,
     pub label: String,
-    pub properties: HashMap<String, String>,
+    pub properties: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -60,29 +128,48 @@ pub struct GraphEdge {
     pub from: String,
     pub to: String,
     pub relationship: String,
-    pub properties: HashMap<String, String>,
+    pub properties: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ContainerManifest {
+    pub format_version: String,
+    pub document_metadata: DocumentMetadata,
+    pub content_items: usize,
+    pub vectors: usize,
+    pub graph_nodes: usize,
+    pub graph_edges: usize,
+    pub files: Vec<String>,
 }
 
-#[wasm_bindgen]
 pub struct MadDocument {
     metadata: DocumentMetadata,
     content_items: Vec<ContentItem>,
     vectors: Vec<VectorEmbedding>,
     graph_db: GraphDatabase,
+    vector_index: Option<HnswIndex>,
+    text_index: Option<TextIndex>,
 }
 
-#[wasm_bindgen]
 impl MadDocument {
-    #[wasm_bindgen(constructor)]
     pub fn new(title: &str, author: &str) -> MadDocument {
-        let id = uuid::Uuid::new_v4().to_string();
+        MadDocument::with_clock(title, author, &SystemClock)
+    }
+
+    pub fn with_clock(title: &str, author: &str, clock: &dyn Clock) -> MadDocument {
         let metadata = DocumentMetadata {
-            id: id.clone(),
+            id: uuid::Uuid::new_v4().to_string(),
             title: title.to_string(),
             author: author.to_string(),
-            created: js_sys::Date::now() as u64,
+            authors: Vec::new(),
+            created: clock.now_millis(),
+            modified: None,
             content_hash: String::new(),
             version: "1.0".to_string(),
+            embedding_model: None,
+            embedding_dimension: None,
+            vector_quantization: None,
+            keep_full_precision: None,
         };
 
         MadDocument {
@@ -90,125 +177,678 @@ impl MadDocument {
             content_items: Vec::new(),
             vectors: Vec::new(),
             graph_db: GraphDatabase::new(),
+            vector_index: None,
+            text_index: None,
         }
     }
 
-    #[wasm_bindgen]
-    pub fn init_databases(&mut self) -> Result<(), JsValue> {
+    pub fn init_databases(&mut self) -> MadResult<()> {
         // No initialization needed for in-memory storage
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn add_content(&mut self, content_type: &str, data: &[u8], text_content: &str) -> Result<String, JsValue> {
-        let id = uuid::Uuid::new_v4().to_string();
-        
+    pub fn add_content(&mut self, content_type: &str, data: &[u8], text_content: &str) -> MadResult<String> {
         let content_item = ContentItem {
-            id: id.clone(),
+            id: uuid::Uuid::new_v4().to_string(),
             content_type: content_type.to_string(),
-            data: base64::encode(data),
+            data: BASE64.encode(data),
             text_content: text_content.to_string(),
             metadata: HashMap::new(),
         };
         
-        self.content_items.push(content_item);
-        Ok(id)
+        self.add_content_item(content_item)
+    }
+
+    pub fn add_vector_embedding(&mut self, content_id: &str, content: &str, embedding: &[f32]) -> MadResult<()> {
+        self.add_vector_embedding_with_metadata(content_id, content, embedding, HashMap::new())
     }
 
-    #[wasm_bindgen]
-    pub fn add_vector_embedding(&mut self, content_id: &str, content: &str, embedding: &[f32]) {
+    // Embeddings inherit the item's content_type and any page/chapter/section/
+    // language metadata it carries; explicit metadata takes precedence
+    pub fn add_vector_embedding_with_metadata(
+        &mut self,
+        content_id: &str,
+        content: &str,
+        embedding: &[f32],
+        metadata: HashMap<String, String>,
+    ) -> MadResult<()> {
+        let item = self.content_item(content_id)
+            .ok_or_else(|| MadError::NotFound(format!("Content item {}", content_id)))?;
+        if embedding.is_empty() {
+            return Err(MadError::InvalidInput(format!("Empty embedding for {}", content_id)));
+        }
+        self.check_dimension(embedding.len())?;
+
+        let mut inherited: HashMap<String, String> = EMBEDDING_METADATA_KEYS.iter()
+            .filter_map(|key| item.metadata.get(*key).map(|value| (key.to_string(), value.clone())))
+            .collect();
+        inherited.insert("content_type".to_string(), item.content_type.clone());
+        inherited.extend(metadata);
+
+        // Quantized documents that dropped full precision keep dropping it
+        let quantized = self.metadata.vector_quantization.map(|q| QuantizedVector::encode(embedding, q));
+        let full_precision = quantized.is_none() || self.metadata.keep_full_precision != Some(false);
         let vector = VectorEmbedding {
             id: content_id.to_string(),
             content: content.to_string(),
-            embedding: embedding.to_vec(),
-            metadata: HashMap::new(),
+            embedding: if full_precision { embedding.to_vec() } else { Vec::new() },
+            metadata: inherited,
+            quantized,
         };
+        if let Some(index) = &mut self.vector_index {
+            index.insert(&vector.search_vector());
+        }
         self.vectors.push(vector);
+        self.metadata.embedding_dimension.get_or_insert(embedding.len());
+        Ok(())
+    }
+
+    // Record the model behind this document's embeddings. Fails if existing
+    // embeddings have a different dimension.
+    pub fn set_embedding_model(&mut self, model: &str, dimension: usize) -> MadResult<()> {
+        if let Some(vector) = self.vectors.iter().find(|v| v.dimension() != dimension) {
+            return Err(MadError::DimensionMismatch { expected: dimension, actual: vector.dimension() });
+        }
+        self.metadata.embedding_model = Some(model.to_string());
+        self.metadata.embedding_dimension = Some(dimension);
+        Ok(())
+    }
+
+    // Recorded dimension, or that of the first embedding for documents that predate it
+    pub fn embedding_dimension(&self) -> Option<usize> {
+        self.metadata.embedding_dimension
+            .or_else(|| self.vectors.first().map(VectorEmbedding::dimension))
+    }
+
+    fn check_dimension(&self, actual: usize) -> MadResult<()> {
+        match self.embedding_dimension() {
+            Some(expected) if expected != actual => Err(MadError::DimensionMismatch { expected, actual }),
+            _ => Ok(()),
+        }
     }
 
-    #[wasm_bindgen]
-    pub fn add_graph_node(&mut self, id: &str, label: &str, properties_json: &str) -> Result<(), JsValue> {
-        self.graph_db.add_node(id, label, properties_json)
+    // Index every embedding for approximate nearest-neighbour search; the
+    // index is kept up to date by later add_vector_embedding calls
+    pub fn build_vector_index(&mut self, params: HnswParams) {
+        let vectors: Vec<Cow<[f32]>> = self.vectors.iter().map(VectorEmbedding::search_vector).collect();
+        self.vector_index = Some(HnswIndex::build(params, vectors.iter().map(|v| v.as_ref())));
     }
 
-    #[wasm_bindgen]
-    pub fn add_graph_edge(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> Result<String, JsValue> {
-        self.graph_db.add_edge(from, to, relationship, properties_json)
+    // Store every embedding in quantized form (and quantize later additions).
+    // Search then runs on the quantized vectors; with `keep_full_precision`
+    // the full vectors are kept to rescore the candidates, otherwise they are
+    // dropped to shrink the container.
+    pub fn quantize_vectors(&mut self, quantization: Quantization, keep_full_precision: bool) {
+        for vector in &mut self.vectors {
+            let quantized = QuantizedVector::encode(&vector.full_vector(), quantization);
+            vector.quantized = Some(quantized);
+            if !keep_full_precision {
+                vector.embedding.clear();
+            }
+        }
+        self.metadata.vector_quantization = Some(quantization);
+        self.metadata.keep_full_precision = Some(keep_full_precision);
+        if let Some(params) = self.vector_index.as_ref().map(HnswIndex::params) {
+            self.build_vector_index(params);
+        }
+    }
+
+    // Graph edits keep the content hash current
+    pub fn add_graph_node(&mut self, id: &str, label: &str, properties_json: &str) -> MadResult<()> {
+        self.graph_db.add_node(id, label, properties_json)?;
+        self.calculate_content_hash();
+        Ok(())
+    }
+
+    pub fn add_graph_edge(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> MadResult<String> {
+        let edge_id = self.graph_db.add_edge(from, to, relationship, properties_json)?;
+        self.calculate_content_hash();
+        Ok(edge_id)
     }
 
-    #[wasm_bindgen]
-    pub fn query_graph(&self, start_node: &str, max_depth: usize, relationship_filter: Option<String>) -> String {
+    pub fn merge_graph_node(&mut self, id: &str, label: &str, properties_json: &str) -> MadResult<bool> {
+        let created = self.graph_db.merge_node(id, label, properties_json)?;
+        self.calculate_content_hash();
+        Ok(created)
+    }
+
+    pub fn update_graph_node(&mut self, id: &str, properties_json: &str) -> MadResult<()> {
+        self.graph_db.update_node_properties(id, properties_json)?;
+        self.calculate_content_hash();
+        Ok(())
+    }
+
+    pub fn remove_graph_node(&mut self, id: &str) -> MadResult<usize> {
+        let removed_edges = self.graph_db.remove_node(id)?;
+        self.calculate_content_hash();
+        Ok(removed_edges)
+    }
+
+    pub fn remove_graph_edge(&mut self, edge_id: &str) -> MadResult<()> {
+        self.graph_db.remove_edge(edge_id)?;
+        self.calculate_content_hash();
+        Ok(())
+    }
+
+    pub fn query_graph(&self, start_node: &str, max_depth: usize, relationship_filter: Option<&str>) -> String {
         self.graph_db.traverse(start_node, max_depth, relationship_filter)
     }
 
-    #[wasm_bindgen]
-    pub fn find_path(&self, start: &str, end: &str, relationship_filter: Option<String>) -> String {
+    pub fn find_path(&self, start: &str, end: &str, relationship_filter: Option<&str>) -> String {
         self.graph_db.find_shortest_path(start, end, relationship_filter)
     }
 
-    #[wasm_bindgen]
-    pub fn search_content(&self, query: &str) -> Result<String, JsValue> {
-        let query_lower = query.to_lowercase();
-        let results: Vec<String> = self.content_items
-            .iter()
-            .filter(|item| item.text_content.to_lowercase().contains(&query_lower))
-            .map(|item| {
-                format!(
-                    "{{\"id\": \"{}\", \"content_type\": \"{}\"}}",
-                    item.id, item.content_type
-                )
+    pub fn query_cypher(&self, query: &str) -> MadResult<String> {
+        self.graph_db.query_cypher(query)
+    }
+
+    pub fn search_content(&self, query: &str) -> MadResult<String> {
+        let hits = self.search_text(query, self.content_items.len());
+        Ok(serde_json::to_string(&hits)?)
+    }
+
+    // One page of search_text results; pass the returned cursor back for the next
+    pub fn search_text_page(&self, query: &str, cursor: Option<&str>, limit: usize) -> MadResult<Page<TextSearchHit>> {
+        let offset = pagination::offset(cursor)?;
+        let hits = self.search_text(query, offset.saturating_add(limit).saturating_add(1));
+        Ok(Page::slice(hits, offset, limit))
+    }
+
+    // BM25-ranked full-text search over text_content (see text_index for the query syntax)
+    pub fn search_text(&self, query: &str, limit: usize) -> Vec<TextSearchHit> {
+        self.text_matches(query, limit)
+            .into_iter()
+            .map(|m| {
+                let item = &self.content_items[m.doc];
+                let (snippet, highlights) = text_index::highlight(&item.text_content, &m.tokens);
+                TextSearchHit {
+                    id: item.id.clone(),
+                    content_type: item.content_type.clone(),
+                    score: m.score,
+                    snippet,
+                    highlights,
+                }
             })
-            .collect();
+            .collect()
+    }
 
-        Ok(format!("[{}]", results.join(",")))
+    fn text_matches(&self, query: &str, limit: usize) -> Vec<text_index::TextMatch> {
+        match &self.text_index {
+            Some(index) => index.search(query, limit),
+            None => {
+                let texts = self.content_items.iter().map(|item| item.text_content.as_str());
+                TextIndex::build(texts).search(query, limit)
+            }
+        }
     }
 
-    #[wasm_bindgen]
-    pub fn vector_similarity_search(&self, query_embedding: &[f32], top_k: usize) -> String {
-        let mut similarities: Vec<(f32, &VectorEmbedding)> = self.vectors
-            .iter()
-            .map(|v| {
-                let similarity = cosine_similarity(query_embedding, &v.embedding);
-                (similarity, v)
+    // Fuse BM25 and vector rankings. Without a query embedding only the text side is used.
+    pub fn hybrid_search(&self, query: &str, query_embedding: Option<&[f32]>, options: &HybridSearchOptions) -> MadResult<Vec<HybridSearchHit>> {
+        let filter = &options.filter;
+        let allowed = |item: &ContentItem| filter.is_empty() || filter.matches(item);
+
+        // Rank every lexical match so filtering can't starve the result list
+        let text_matches: Vec<text_index::TextMatch> = self.text_matches(query, self.content_items.len())
+            .into_iter()
+            .filter(|m| allowed(&self.content_items[m.doc]))
+            .collect();
+        let text_ranking: Vec<(String, f32)> = text_matches.iter()
+            .map(|m| (self.content_items[m.doc].id.clone(), m.score))
+            .collect();
+
+        let mut vector_ranking: Vec<(String, f32)> = Vec::new();
+        if let Some(embedding) = query_embedding {
+            let depth = options.top_k.saturating_mul(5).max(50);
+            let vector_filter = |v: &VectorEmbedding| self.content_item(&v.id).is_some_and(allowed);
+            let predicate: Option<&dyn Fn(&VectorEmbedding) -> bool> = if filter.is_empty() { None } else { Some(&vector_filter) };
+
+            // Several embeddings may belong to one item; keep its best
+            for (score, vector) in self.nearest_vectors(embedding, depth, options.ef, predicate)? {
+                if !vector_ranking.iter().any(|(id, _)| *id == vector.id) {
+                    vector_ranking.push((vector.id.clone(), score));
+                }
+            }
+        }
+
+        let hits = search::fuse(&text_ranking, &vector_ranking, options.fusion)
+            .into_iter()
+            .take(options.top_k)
+            .filter_map(|(id, score)| {
+                let item = self.content_item(&id)?;
+                let text_rank = text_ranking.iter().position(|(t, _)| *t == id);
+                let vector_rank = vector_ranking.iter().position(|(v, _)| *v == id);
+                let tokens = text_rank.map(|rank| text_matches[rank].tokens.as_slice()).unwrap_or(&[]);
+                let (snippet, _) = text_index::highlight(&item.text_content, tokens);
+
+                Some(HybridSearchHit {
+                    id: item.id.clone(),
+                    content_type: item.content_type.clone(),
+                    score,
+                    text_rank: text_rank.map(|rank| rank + 1),
+                    text_score: text_rank.map(|rank| text_ranking[rank].1),
+                    vector_rank: vector_rank.map(|rank| rank + 1),
+                    vector_score: vector_rank.map(|rank| vector_ranking[rank].1),
+                    snippet,
+                })
             })
             .collect();
+        Ok(hits)
+    }
 
-        similarities.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-        similarities.truncate(top_k);
+    // Index content text for search_content; kept up to date by add_content_item
+    pub fn build_text_index(&mut self) {
+        let texts = self.content_items.iter().map(|item| item.text_content.as_str());
+        self.text_index = Some(TextIndex::build(texts));
+    }
+
+    pub fn vector_similarity_search(&self, query_embedding: &[f32], top_k: usize) -> MadResult<String> {
+        self.vector_similarity_search_with_ef(query_embedding, top_k, None)
+    }
 
-        let results: Vec<String> = similarities
-            .iter()
+    // `ef` trades recall for speed on indexed documents (None = index default)
+    pub fn vector_similarity_search_with_ef(&self, query_embedding: &[f32], top_k: usize, ef: Option<usize>) -> MadResult<String> {
+        self.vector_search_json(query_embedding, top_k, ef, None)
+    }
+
+    // Only embeddings whose metadata matches `filter` are ranked
+    pub fn vector_similarity_search_filtered(&self, query_embedding: &[f32], top_k: usize, filter: &MetadataFilter, ef: Option<usize>) -> MadResult<String> {
+        self.vector_search_json(query_embedding, top_k, ef, Some(filter))
+    }
+
+    // Ranked passages for a query embedding. `query_text`, when given, is
+    // highlighted in the excerpts.
+    pub fn vector_search(&self, query_embedding: &[f32], options: &VectorSearchOptions, query_text: Option<&str>) -> MadResult<Vec<VectorSearchHit>> {
+        let predicate = options.filter.as_ref().map(|filter| move |v: &VectorEmbedding| filter.matches(&v.metadata));
+        let similarities = self.nearest_vectors(
+            query_embedding,
+            options.top_k,
+            options.ef,
+            predicate.as_ref().map(|p| p as &dyn Fn(&VectorEmbedding) -> bool),
+        )?;
+
+        Ok(similarities.into_iter()
             .map(|(score, v)| {
-                format!(
-                    "{{\"id\": \"{}\", \"content\": \"{}\", \"score\": {}}}",
-                    v.id, v.content, score
-                )
+                let item = self.content_item(&v.id);
+                let text = item.map(|item| item.text_content.as_str()).unwrap_or(&v.content);
+                let offset = |key: &str| v.metadata.get(key).and_then(|value| value.parse().ok());
+                VectorSearchHit {
+                    id: v.id.clone(),
+                    content_type: item.map(|item| item.content_type.clone()).unwrap_or_default(),
+                    score,
+                    excerpt: text_index::excerpt(text, query_text),
+                    parent_id: v.metadata.get(chunker::PARENT_ID_KEY).cloned(),
+                    char_start: offset(chunker::CHAR_START_KEY),
+                    char_end: offset(chunker::CHAR_END_KEY),
+                    heading: v.metadata.get(chunker::HEADING_KEY).cloned(),
+                }
+            })
+            .collect())
+    }
+
+    // Embed `query` the way this document's embeddings were made, then search
+    pub fn semantic_search(&self, query: &str, options: &VectorSearchOptions, provider: Option<&dyn EmbeddingProvider>) -> MadResult<Vec<VectorSearchHit>> {
+        let embedding = self.embed_query(query, provider)?;
+        self.vector_search(&embedding, options, Some(query))
+    }
+
+    // Uses `provider` when it is the recorded model, and the offline hashed
+    // encoder for documents built with it. Other models can't be reproduced
+    // offline, so they need a matching provider. Without a recorded model the
+    // vectors' space is unknown and the caller has to pass an embedding.
+    pub fn embed_query(&self, query: &str, provider: Option<&dyn EmbeddingProvider>) -> MadResult<Vec<f32>> {
+        let Some(recorded) = self.metadata.embedding_model.as_deref() else {
+            return Err(MadError::Unsupported(
+                "document has no query encoder; pass `embedding`".to_string()
+            ));
+        };
+        if let Some(provider) = provider.filter(|p| p.model() == recorded) {
+            return provider.embed(&[query])?
+                .pop()
+                .ok_or_else(|| MadError::Provider("no embedding returned for the query".to_string()));
+        }
+
+        match recorded {
+            HashedEmbeddingProvider::MODEL => {
+                let dimension = self.embedding_dimension().unwrap_or(HashedEmbeddingProvider::DEFAULT_DIMENSION);
+                Ok(HashedEmbeddingProvider::new(dimension).embed_text(query))
+            }
+            model => Err(MadError::Unsupported(format!(
+                "text queries need an embedding provider for model '{}'; pass `embedding`",
+                model
+            ))),
+        }
+    }
+
+    fn vector_search_json(&self, query_embedding: &[f32], top_k: usize, ef: Option<usize>, filter: Option<&MetadataFilter>) -> MadResult<String> {
+        let predicate = filter.map(|filter| move |v: &VectorEmbedding| filter.matches(&v.metadata));
+        let similarities = self.nearest_vectors(
+            query_embedding,
+            top_k,
+            ef,
+            predicate.as_ref().map(|p| p as &dyn Fn(&VectorEmbedding) -> bool),
+        )?;
+
+        let results: Vec<VectorMatch> = similarities
+            .into_iter()
+            .map(|(score, v)| VectorMatch {
+                id: v.id.clone(),
+                content: v.content.clone(),
+                score,
             })
             .collect();
 
-        format!("[{}]", results.join(","))
+        Ok(serde_json::to_string(&results)?)
+    }
+
+    // A filter switches to an exact scan over the matching vectors, so it is
+    // applied before top-k rather than thinning out the approximate results.
+    // Queries must match the document's embedding dimension.
+    fn nearest_vectors(
+        &self,
+        query_embedding: &[f32],
+        top_k: usize,
+        ef: Option<usize>,
+        filter: Option<&dyn Fn(&VectorEmbedding) -> bool>,
+    ) -> MadResult<Vec<(f32, &VectorEmbedding)>> {
+        self.check_dimension(query_embedding.len())?;
+
+        // Quantized search over-fetches so rescoring at full precision can reorder
+        let depth = match self.metadata.vector_quantization {
+            Some(Quantization::Int8) => top_k.saturating_mul(4),
+            Some(Quantization::Binary) => top_k.saturating_mul(10),
+            None => top_k,
+        };
+        let rescore = depth > top_k;
+        let query = vector_index::normalize(query_embedding);
+
+        let mut similarities: Vec<(f32, &VectorEmbedding)> = match (&self.vector_index, filter) {
+            (Some(index), None) => index.search(&query, depth, ef)
+                .into_iter()
+                .map(|(node, similarity)| (similarity, &self.vectors[node]))
+                .collect(),
+            // Indexed vectors are already normalized; otherwise compute cosine directly
+            _ => self.vectors
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| filter.is_none_or(|f| f(v)))
+                .map(|(node, v)| match &self.vector_index {
+                    Some(index) => (index.similarity(&query, node), v),
+                    None => (cosine_similarity(&query, &v.search_vector()), v),
+                })
+                .collect(),
+        };
+
+        if rescore {
+            for (score, v) in similarities.iter_mut() {
+                if v.quantized.is_some() && !v.embedding.is_empty() {
+                    *score = cosine_similarity(&query, &v.embedding);
+                }
+            }
+        }
+        similarities.sort_by(|a, b| b.0.total_cmp(&a.0));
+        similarities.truncate(top_k);
+        Ok(similarities)
     }
 
-    #[wasm_bindgen]
     pub fn get_metadata(&self) -> String {
         serde_json::to_string(&self.metadata).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
+    pub fn export_markdown(&self, include_metadata: bool) -> String {
+        export::to_markdown(self, include_metadata)
+    }
+
+    pub fn export_html(&self, include_metadata: bool) -> String {
+        export::to_html(self, include_metadata)
+    }
+
+    pub fn export_json(&self) -> MadResult<String> {
+        export::to_json(self)
+    }
+
+    pub fn from_adam_json(adam_json: &str) -> MadResult<MadDocument> {
+        adam::import(adam_json)
+    }
+
+    pub fn to_adam_json(&self) -> MadResult<String> {
+        adam::export(self)
+    }
+
     pub fn calculate_content_hash(&mut self) -> String {
         use sha2::{Sha256, Digest};
         
         let mut hasher = Sha256::new();
-        hasher.update(serde_json::to_string(&self.metadata).unwrap_or_default());
-        hasher.update(serde_json::to_string(&self.content_items).unwrap_or_default());
-        hasher.update(serde_json::to_string(&self.vectors).unwrap_or_default());
-        hasher.update(self.graph_db.export_cypher());
+        // The previous hash is not part of the content
+        let metadata = DocumentMetadata { content_hash: String::new(), ..self.metadata.clone() };
+        // Going through serde_json::Value sorts map keys, so equal documents
+        // hash the same whatever order their HashMaps iterate in
+        let canonical = |value: serde_json::Result<serde_json::Value>| value.map(|v| v.to_string()).unwrap_or_default();
+        hasher.update(canonical(serde_json::to_value(&metadata)));
+        hasher.update(canonical(serde_json::to_value(&self.content_items)));
+        hasher.update(canonical(serde_json::to_value(&self.vectors)));
+        hasher.update(canonical(serde_json::to_value(self.graph_db.snapshot())));
         
         let result = hasher.finalize();
         let hash = hex::encode(result);
         self.metadata.content_hash = hash.clone();
         hash
     }
+
+    pub fn to_bytes(&self) -> MadResult<Vec<u8>> {
+        let content_json = serde_json::to_vec(&self.content_items)?;
+        let vectors_json = serde_json::to_vec(&self.vectors)?;
+        let graph_json = self.graph_db.export_json().into_bytes();
+
+        let mut files = vec![
+            container::CONTENT_FILE.to_string(),
+            container::VECTORS_FILE.to_string(),
+            container::GRAPH_FILE.to_string(),
+        ];
+        if self.vector_index.is_some() {
+            files.push(container::VECTOR_INDEX_FILE.to_string());
+        }
+        if self.text_index.is_some() {
+            files.push(container::TEXT_INDEX_FILE.to_string());
+        }
+
+        let manifest = ContainerManifest {
+            format_version: container::FORMAT_VERSION.to_string(),
+            document_metadata: self.metadata.clone(),
+            content_items: self.content_items.len(),
+            vectors: self.vectors.len(),
+            graph_nodes: self.graph_db.node_count(),
+            graph_edges: self.graph_db.edge_count(),
+            files,
+        };
+        let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+        let mut mad = MadContainer::new();
+        mad.insert(container::MANIFEST_FILE, manifest_json);
+        mad.insert(container::CONTENT_FILE, content_json);
+        mad.insert(container::VECTORS_FILE, vectors_json);
+        mad.insert(container::GRAPH_FILE, graph_json);
+        if let Some(index) = &self.vector_index {
+            mad.insert(container::VECTOR_INDEX_FILE, serde_json::to_vec(index)?);
+        }
+        if let Some(index) = &self.text_index {
+            mad.insert(container::TEXT_INDEX_FILE, serde_json::to_vec(index)?);
+        }
+
+        mad.to_bytes(self.metadata.created / 1000)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> MadResult<MadDocument> {
+        let mad = MadContainer::from_bytes(bytes)?;
+
+        let manifest: ContainerManifest = parse_entry(&mad, container::MANIFEST_FILE)?;
+        if manifest.format_version != container::FORMAT_VERSION {
+            return Err(MadError::Unsupported(format!(
+                "container format version {}",
+                manifest.format_version
+            )));
+        }
+
+        let content_items: Vec<ContentItem> = parse_entry(&mad, container::CONTENT_FILE)?;
+        let vectors: Vec<VectorEmbedding> = parse_entry(&mad, container::VECTORS_FILE)?;
+        if let Some(dimension) = manifest.document_metadata.embedding_dimension {
+            if let Some(vector) = vectors.iter().find(|v| v.dimension() != dimension) {
+                return Err(MadError::IntegrityFailure(format!(
+                    "embedding for {} has {} dimensions, document records {}",
+                    vector.id,
+                    vector.dimension(),
+                    dimension
+                )));
+            }
+        }
+        for quantized in vectors.iter().filter_map(|v| v.quantized.as_ref()) {
+            quantized.check()?;
+        }
+        let graph_json = std::str::from_utf8(mad.require(container::GRAPH_FILE)?)
+            .map_err(|e| MadError::Container(format!("{}: {}", container::GRAPH_FILE, e)))?;
+        let graph_db = GraphDatabase::import_json(graph_json)?;
+        check_consistency(&manifest, &content_items, &vectors, &graph_db)?;
+
+        // Older containers have no index; search falls back to a linear scan
+        let vector_index = match mad.get(container::VECTOR_INDEX_FILE) {
+            Some(_) => {
+                let mut index: HnswIndex = parse_entry(&mad, container::VECTOR_INDEX_FILE)?;
+                let search_vectors: Vec<Cow<[f32]>> = vectors.iter().map(VectorEmbedding::search_vector).collect();
+                index.attach(search_vectors.iter().map(|v| v.as_ref()))?;
+                Some(index)
+            }
+            None => None,
+        };
+        let text_index = match mad.get(container::TEXT_INDEX_FILE) {
+            Some(_) => {
+                let index: TextIndex = parse_entry(&mad, container::TEXT_INDEX_FILE)?;
+                index.check_len(content_items.len())?;
+                Some(index)
+            }
+            None => None,
+        };
+
+        Ok(MadDocument {
+            metadata: manifest.document_metadata,
+            content_items,
+            vectors,
+            graph_db,
+            vector_index,
+            text_index,
+        })
+    }
+
+    pub fn metadata(&self) -> &DocumentMetadata {
+        &self.metadata
+    }
+
+    pub fn content_items(&self) -> &[ContentItem] {
+        &self.content_items
+    }
+
+    pub fn content_item(&self, id: &str) -> Option<&ContentItem> {
+        self.content_items.iter().find(|item| item.id == id)
+    }
+
+    // Chunks split from a content item, in order
+    pub fn chunks(&self, parent_id: &str) -> Vec<&ContentItem> {
+        let mut chunks: Vec<&ContentItem> = self.content_items.iter()
+            .filter(|item| item.metadata.get(chunker::PARENT_ID_KEY).map(String::as_str) == Some(parent_id))
+            .collect();
+        chunks.sort_by_key(|item| {
+            item.metadata.get(chunker::CHUNK_INDEX_KEY)
+                .and_then(|index| index.parse::<usize>().ok())
+                .unwrap_or(usize::MAX)
+        });
+        chunks
+    }
+
+    pub fn vector_index(&self) -> Option<&HnswIndex> {
+        self.vector_index.as_ref()
+    }
+
+    pub fn vectors(&self) -> &[VectorEmbedding] {
+        &self.vectors
+    }
+
+    pub fn graph(&self) -> &GraphDatabase {
+        &self.graph_db
+    }
+
+    // Insert a fully-formed item, keeping its ID (used by the builder and importers)
+    pub fn add_content_item(&mut self, item: ContentItem) -> MadResult<String> {
+        if self.content_items.iter().any(|existing| existing.id == item.id) {
+            return Err(MadError::AlreadyExists(format!("Content item {}", item.id)));
+        }
+
+        let id = item.id.clone();
+        if let Some(index) = &mut self.text_index {
+            index.insert(&item.text_content);
+        }
+        self.content_items.push(item);
+        Ok(id)
+    }
+}
+
+// Content item metadata copied onto embeddings for filtering
+const EMBEDDING_METADATA_KEYS: &[&str] = &[
+    "page",
+    "chapter",
+    "section",
+    "section_type",
+    "language",
+    chunker::PARENT_ID_KEY,
+    chunker::CHUNK_INDEX_KEY,
+    chunker::CHAR_START_KEY,
+    chunker::CHAR_END_KEY,
+    chunker::HEADING_KEY,
+];
+
+// The checksums only show the files weren't changed after writing; this
+// catches containers written inconsistently in the first place
+fn check_consistency(
+    manifest: &ContainerManifest,
+    content_items: &[ContentItem],
+    vectors: &[VectorEmbedding],
+    graph_db: &GraphDatabase,
+) -> MadResult<()> {
+    let counts = [
+        ("content items", manifest.content_items, content_items.len()),
+        ("vectors", manifest.vectors, vectors.len()),
+        ("graph nodes", manifest.graph_nodes, graph_db.node_count()),
+        ("graph edges", manifest.graph_edges, graph_db.edge_count()),
+    ];
+    for (name, recorded, loaded) in counts {
+        if recorded != loaded {
+            return Err(MadError::IntegrityFailure(format!(
+                "manifest records {} {}, container holds {}",
+                recorded, name, loaded
+            )));
+        }
+    }
+
+    let content_ids: HashSet<&str> = content_items.iter().map(|item| item.id.as_str()).collect();
+    for vector in vectors {
+        if !content_ids.contains(vector.id.as_str()) {
+            return Err(MadError::IntegrityFailure(format!("embedding for missing content item {}", vector.id)));
+        }
+        if let Some(quantized) = &vector.quantized {
+            if !vector.embedding.is_empty() && quantized.dimension != vector.embedding.len() {
+                return Err(MadError::IntegrityFailure(format!(
+                    "quantized vector for {} has {} dimensions, embedding has {}",
+                    vector.id,
+                    quantized.dimension,
+                    vector.embedding.len()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_entry<T: serde::de::DeserializeOwned>(mad: &MadContainer, name: &str) -> MadResult<T> {
+    let data = mad.require(name)?;
+    serde_json::from_slice(data)
+        .map_err(|e| MadError::InvalidJson(format!("{}: {}", name, e)))
 }
 
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
@@ -227,7 +867,190 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     }
 }
 
-#[wasm_bindgen(start)]
-pub fn main() {
-    console_log!("MAD Runtime initialized");
-}
\ No newline at end of file
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn built_document() -> MadDocument {
+        let mut builder = MadBuilder::with_clock("Field Notes", "Ada", Arc::new(FixedClock(1_700_000_000_000)));
+        let intro = builder.add_text_content("Graph databases store entities and relationships.", "text/plain").unwrap();
+        let vectors = builder.add_text_content("Vector search ranks passages by embedding similarity.", "text/plain").unwrap();
+        builder.set_embedding_provider(Box::new(HashedEmbeddingProvider::new(32))).unwrap();
+        builder.embed_content().unwrap();
+        builder.create_entity("graphs", "Topic", r#"{"weight": 0.5, "tags": ["db"]}"#).unwrap();
+        builder.add_citation(&vectors, &intro, "background").unwrap();
+        builder.set_vector_quantization(Some(Quantization::Int8), true);
+        builder.build().unwrap();
+        builder.get_document()
+    }
+
+    fn value<T: Serialize>(value: &T) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    // Rewrites one JSON entry and repacks the container with fresh checksums
+    fn repack(bytes: &[u8], name: &str, edit: impl FnOnce(&mut serde_json::Value)) -> Vec<u8> {
+        let mut mad = MadContainer::from_bytes(bytes).unwrap();
+        let mut json: serde_json::Value = serde_json::from_slice(mad.require(name).unwrap()).unwrap();
+        edit(&mut json);
+        mad.insert(name, serde_json::to_vec(&json).unwrap());
+        mad.to_bytes(0).unwrap()
+    }
+
+    #[test]
+    fn native_build_save_load_round_trip() {
+        let doc = built_document();
+        let loaded = MadDocument::from_bytes(&doc.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(value(loaded.metadata()), value(doc.metadata()));
+        assert_eq!(value(&loaded.content_items), value(&doc.content_items));
+        assert_eq!(value(&loaded.vectors), value(&doc.vectors));
+        assert_eq!(value(&loaded.graph().snapshot()), value(&doc.graph().snapshot()));
+        assert_eq!(loaded.vector_index().map(HnswIndex::len), Some(2));
+        assert_eq!(loaded.metadata().keep_full_precision, Some(true));
+
+        let options = VectorSearchOptions::default();
+        let before = value(&doc.semantic_search("embedding similarity", &options, None).unwrap());
+        assert_eq!(value(&loaded.semantic_search("embedding similarity", &options, None).unwrap()), before);
+        assert_eq!(value(&loaded.search_text("relationships", 5)), value(&doc.search_text("relationships", 5)));
+        assert_eq!(loaded.search_text("relationships", 5)[0].id, doc.content_items[0].id);
+    }
+
+    #[test]
+    fn load_rejects_tampered_containers() {
+        let mut doc = MadDocument::new("t", "a");
+        doc.add_content("text/plain", b"", "Graph databases store entities.").unwrap();
+        let mut bytes = doc.to_bytes().unwrap();
+        assert!(MadDocument::from_bytes(&bytes).is_ok());
+
+        let text = b"Graph databases";
+        let at = bytes.windows(text.len()).position(|window| window == text).unwrap();
+        bytes[at] = b'g';
+        assert!(matches!(MadDocument::from_bytes(&bytes), Err(MadError::IntegrityFailure(_))));
+        assert!(MadDocument::from_bytes(b"not a container").is_err());
+    }
+
+    #[test]
+    fn load_rejects_inconsistent_containers() {
+        let bytes = built_document().to_bytes().unwrap();
+        let inconsistent = [
+            repack(&bytes, container::MANIFEST_FILE, |manifest| manifest["vectors"] = 3.into()),
+            repack(&bytes, container::MANIFEST_FILE, |manifest| manifest["graph_edges"] = 0.into()),
+            repack(&bytes, container::VECTORS_FILE, |vectors| vectors[0]["id"] = "missing".into()),
+            repack(
+                &repack(&bytes, container::MANIFEST_FILE, |manifest| {
+                    manifest["document_metadata"]["embedding_dimension"] = serde_json::Value::Null
+                }),
+                container::VECTORS_FILE,
+                |vectors| {
+                    vectors[0]["embedding"].as_array_mut().unwrap().pop();
+                },
+            ),
+        ];
+        for bytes in inconsistent {
+            assert!(matches!(MadDocument::from_bytes(&bytes), Err(MadError::IntegrityFailure(_))));
+        }
+    }
+
+    #[test]
+    fn checks_embedding_dimensions() {
+        let mut doc = MadDocument::new("t", "a");
+        let id = doc.add_content("text/plain", b"", "alpha").unwrap();
+        doc.add_vector_embedding(&id, "alpha", &[1.0, 0.0, 0.0]).unwrap();
+        assert!(matches!(
+            doc.add_vector_embedding(&id, "alpha", &[1.0, 0.0]),
+            Err(MadError::DimensionMismatch { expected: 3, actual: 2 })
+        ));
+        assert!(doc.vector_search(&[1.0, 0.0], &VectorSearchOptions::default(), None).is_err());
+        assert!(doc.set_embedding_model("other", 2).is_err());
+    }
+
+    #[test]
+    fn quantization_setting_survives_reload() {
+        let mut doc = MadDocument::new("t", "a");
+        doc.quantize_vectors(Quantization::Binary, false);
+        let mut loaded = MadDocument::from_bytes(&doc.to_bytes().unwrap()).unwrap();
+        let id = loaded.add_content("text/plain", b"", "beta").unwrap();
+        loaded.add_vector_embedding(&id, "beta", &[0.5, -0.5]).unwrap();
+        assert!(loaded.vectors()[0].embedding.is_empty());
+        assert!(loaded.vectors()[0].quantized.is_some());
+    }
+
+    #[test]
+    fn rebuilds_follow_the_current_quantization_setting() {
+        let mut builder = MadBuilder::with_clock("t", "a", Arc::new(FixedClock(0)));
+        let id = builder.add_text_content("alpha beta", "text/plain").unwrap();
+        builder.add_vector_embedding(&id, &[0.3, -0.7, 0.1]).unwrap();
+
+        builder.set_vector_quantization(Some(Quantization::Binary), false);
+        builder.build().unwrap();
+        let doc = builder.create_fresh_document().unwrap();
+        assert!(doc.vectors()[0].embedding.is_empty());
+        assert_eq!(doc.metadata().keep_full_precision, Some(false));
+
+        // Each build starts from the previous one's metadata
+        builder.set_vector_quantization(None, true);
+        builder.build().unwrap();
+        let doc = builder.create_fresh_document().unwrap();
+        assert_eq!(doc.vectors()[0].embedding, vec![0.3, -0.7, 0.1]);
+        assert!(doc.vectors()[0].quantized.is_none());
+        assert_eq!(doc.metadata().vector_quantization, None);
+        assert_eq!(doc.metadata().keep_full_precision, None);
+
+        builder.set_vector_quantization(Some(Quantization::Int8), true);
+        builder.build().unwrap();
+        let doc = builder.get_document();
+        assert_eq!(doc.vectors()[0].embedding, vec![0.3, -0.7, 0.1]);
+        assert_eq!(doc.vectors()[0].quantized.as_ref().unwrap().method, Quantization::Int8);
+    }
+
+    #[test]
+    fn embeddings_inherit_chapter_metadata() {
+        let mut doc = MadDocument::new("t", "a");
+        let id = doc.add_content("text/plain", b"", "alpha").unwrap();
+        doc.content_items[0].metadata.insert("chapter".to_string(), "3".to_string());
+        doc.add_vector_embedding(&id, "alpha", &[1.0, 0.0]).unwrap();
+        assert_eq!(doc.vectors()[0].metadata.get("chapter").map(String::as_str), Some("3"));
+    }
+
+    #[test]
+    fn manifest_lists_every_server_tool() {
+        let builder = MadBuilder::with_clock("t", "a", Arc::new(FixedClock(0)));
+        let manifest: serde_json::Value = serde_json::from_str(&builder.export_manifest()).unwrap();
+        let server = McpServer::new();
+        let names: Vec<&str> = server.tools().iter().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(manifest["tools"], serde_json::json!(names));
+        assert!(names.contains(&"mad_cypher_query"));
+    }
+
+    #[test]
+    fn text_queries_need_a_known_encoder() {
+        let mut doc = MadDocument::new("t", "a");
+        let id = doc.add_content("text/plain", b"", "alpha").unwrap();
+        doc.add_vector_embedding(&id, "alpha", &[1.0, 0.0, 0.0]).unwrap();
+        // No recorded model, so a text query can't be embedded
+        assert!(doc.embed_query("alpha", None).is_err());
+
+        doc.set_embedding_model("remote-model", 3).unwrap();
+        assert!(doc.embed_query("alpha", None).is_err());
+        doc.set_embedding_model(HashedEmbeddingProvider::MODEL, 3).unwrap();
+        assert_eq!(doc.embed_query("alpha", None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn content_hash_follows_every_change() {
+        let mut doc = built_document();
+        let built = doc.metadata().content_hash.clone();
+        assert_eq!(doc.calculate_content_hash(), built);
+
+        doc.update_graph_node("graphs", r#"{"weight": 0.75}"#).unwrap();
+        let updated = doc.metadata().content_hash.clone();
+        assert_ne!(updated, built);
+        doc.add_graph_node("extra", "Topic", "").unwrap();
+        assert_ne!(doc.metadata().content_hash, updated);
+        doc.remove_graph_node("extra").unwrap();
+        assert_eq!(doc.metadata().content_hash, updated);
+    }
+}
@@ -1,19 +1,45 @@
-use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use crate::graph_db::GraphQueryResult;
+use crate::pagination::{self, Page};
+use crate::{ContentItem, EmbeddingProvider, FusionMethod, HybridSearchOptions, MadDocument, MadError, MetadataFilter, PropertyFilter, SearchFilter, VectorSearchOptions};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
 #[derive(Serialize, Deserialize)]
 pub struct McpRequest {
     pub method: String,
+    #[serde(default)]
     pub params: HashMap<String, serde_json::Value>,
-    pub id: Option<String>,
+    // JSON-RPC ids may be strings or numbers; requests without one are notifications.
+    // MCP doesn't allow null ids, so `"id": null` is an invalid request.
+    #[serde(default)]
+    pub id: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct McpResponse {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<McpError>,
-    pub id: Option<String>,
+    pub id: Option<serde_json::Value>,
+}
+
+impl McpResponse {
+    fn new(id: Option<serde_json::Value>, result: Result<serde_json::Value, McpError>) -> McpResponse {
+        let (result, error) = match result {
+            Ok(data) => (Some(data), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        McpResponse {
+            jsonrpc: "2.0".to_string(),
+            result,
+            error,
+            id,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -22,36 +48,125 @@ pub struct McpError {
     pub message: String,
 }
 
+// JSON-RPC reserves -32000..-32099 for server errors. MCP's -32002 ("resource
+// not found") is only used by resources/read, see resource_not_found.
+impl From<MadError> for McpError {
+    fn from(e: MadError) -> Self {
+        let code = match e {
+            MadError::NotFound(_)
+            | MadError::InvalidJson(_)
+            | MadError::InvalidInput(_)
+            | MadError::DimensionMismatch { .. }
+            | MadError::Unsupported(_) => -32602,
+            MadError::IntegrityFailure(_) | MadError::Container(_) | MadError::Provider(_) => -32603,
+            MadError::AlreadyExists(_) | MadError::Expired(_) => -32000,
+        };
+
+        McpError {
+            code,
+            message: e.to_string(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Tool {
     pub name: String,
     pub description: String,
+    #[serde(rename = "inputSchema")]
     pub input_schema: serde_json::Value,
 }
 
+// Result payload of mad_search, mad_vector_search and mad_hybrid_search
+#[derive(Serialize)]
+struct SearchResults<'a, T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query: Option<&'a str>,
+    count: usize,
+    results: Vec<T>,
+    #[serde(rename = "nextCursor", skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+impl<'a, T> SearchResults<'a, T> {
+    fn new(query: Option<&'a str>, page: Page<T>) -> Self {
+        SearchResults {
+            query,
+            count: page.results.len(),
+            results: page.results,
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GraphQueryPayload {
+    mode: &'static str,
+    node_count: usize,
+    edge_count: usize,
+    path_found: bool,
+    result: GraphQueryResult,
+    #[serde(rename = "nextCursor", skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CypherPayload {
+    columns: Vec<String>,
+    count: usize,
+    rows: Vec<Vec<serde_json::Value>>,
+    #[serde(rename = "nextCursor", skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+// Caps that keep responses small enough for a client's context window. Larger
+// results are split into pages linked by `nextCursor`.
+#[derive(Clone, Copy, Debug)]
+pub struct McpLimits {
+    // Largest page the search tools return, whatever `limit`/`top_k` asks for
+    pub max_results: usize,
+    // Nodes plus edges per page of mad_graph_query traversals and property matches
+    pub max_graph_items: usize,
+    // Entries per page of tools/list and resources/list
+    pub list_page_size: usize,
+}
+
+impl Default for McpLimits {
+    fn default() -> Self {
+        McpLimits {
+            max_results: 50,
+            max_graph_items: 100,
+            list_page_size: 100,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Resource {
     pub uri: String,
     pub name: String,
     pub description: String,
+    #[serde(rename = "mimeType")]
     pub mime_type: String,
 }
 
-#[wasm_bindgen]
 pub struct McpServer {
-    document: Option<crate::MadDocument>,
+    document: Option<MadDocument>,
+    // Embeds text queries when the document's model isn't the hashed encoder
+    embedding_provider: Option<Box<dyn EmbeddingProvider>>,
     tools: Vec<Tool>,
     resources: Vec<Resource>,
+    limits: McpLimits,
 }
 
-#[wasm_bindgen]
 impl McpServer {
-    #[wasm_bindgen(constructor)]
     pub fn new() -> McpServer {
         let mut server = McpServer {
             document: None,
+            embedding_provider: None,
             tools: Vec::new(),
             resources: Vec::new(),
+            limits: McpLimits::default(),
         };
         server.init_tools();
         server
@@ -61,13 +176,22 @@ impl McpServer {
         // Initialize MCP tools for document access
         self.tools.push(Tool {
             name: "mad_search".to_string(),
-            description: "Search document content using full-text search".to_string(),
+            description: "Search document content using BM25-ranked full-text search".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "query": {
                         "type": "string",
-                        "description": "Search query"
+                        "description": "Search terms; use \"quoted phrases\" and prefix* matches"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Results per page (capped by the server)",
+                        "default": 10
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "nextCursor from a previous call, to fetch the following page"
                     }
                 },
                 "required": ["query"]
@@ -76,28 +200,106 @@ impl McpServer {
 
         self.tools.push(Tool {
             name: "mad_vector_search".to_string(),
-            description: "Search document content using vector similarity".to_string(),
+            description: "Semantic search: returns the passages closest in meaning to a text query or embedding".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Text to search for; embedded with the document's embedding model"
+                    },
                     "embedding": {
                         "type": "array",
                         "items": {"type": "number"},
-                        "description": "Query embedding vector"
+                        "description": "Query embedding vector; must match the document's embedding dimension"
                     },
                     "top_k": {
                         "type": "integer",
-                        "description": "Number of results to return",
+                        "description": "Results per page (capped by the server)",
                         "default": 5
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "nextCursor from a previous call, to fetch the following page"
+                    },
+                    "ef": {
+                        "type": "integer",
+                        "description": "HNSW search breadth; higher improves recall at the cost of speed"
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Metadata filter applied before ranking, e.g. \"content_type = 'table' AND chapter = 3\""
+                    }
+                },
+                "anyOf": [
+                    {"required": ["query"]},
+                    {"required": ["embedding"]}
+                ]
+            }),
+        });
+
+        self.tools.push(Tool {
+            name: "mad_hybrid_search".to_string(),
+            description: "Search document content with BM25 and vector similarity combined into one ranking".to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Search terms for the full-text side"
+                    },
+                    "embedding": {
+                        "type": "array",
+                        "items": {"type": "number"},
+                        "description": "Query embedding for the vector side (optional)"
+                    },
+                    "top_k": {
+                        "type": "integer",
+                        "description": "Results per page (capped by the server)",
+                        "default": 10
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "nextCursor from a previous call, to fetch the following page"
+                    },
+                    "fusion": {
+                        "type": "string",
+                        "enum": ["rrf", "weighted"],
+                        "description": "Reciprocal rank fusion or weighted normalized scores",
+                        "default": "rrf"
+                    },
+                    "rrf_k": {
+                        "type": "number",
+                        "description": "RRF rank constant",
+                        "default": 60
+                    },
+                    "text_weight": {
+                        "type": "number",
+                        "description": "Weight of the BM25 score for weighted fusion",
+                        "default": 0.5
+                    },
+                    "vector_weight": {
+                        "type": "number",
+                        "description": "Weight of the vector score for weighted fusion",
+                        "default": 0.5
+                    },
+                    "content_type": {
+                        "type": ["string", "array"],
+                        "items": {"type": "string"},
+                        "description": "Only return items with this content type (or one of these)"
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Metadata filter applied before ranking, e.g. \"content_type = 'table' AND chapter = 3\""
                     }
                 },
-                "required": ["embedding"]
+                "required": ["query"]
             }),
         });
 
         self.tools.push(Tool {
             name: "mad_graph_query".to_string(),
-            description: "Query the document's knowledge graph".to_string(),
+            description: "Query the document's knowledge graph: traverse from a node, find the shortest path between two nodes, or match nodes and edges by properties".to_string(),
             input_schema: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -105,6 +307,10 @@ impl McpServer {
                         "type": "string",
                         "description": "Starting node ID for graph traversal"
                     },
+                    "target_node_id": {
+                        "type": "string",
+                        "description": "Find the shortest path from node_id to this node instead of traversing"
+                    },
                     "relationship": {
                         "type": "string",
                         "description": "Relationship type to follow"
@@ -113,11 +319,50 @@ impl McpServer {
                         "type": "integer",
                         "description": "Maximum traversal depth",
                         "default": 1
+                    },
+                    "node_properties": {
+                        "type": "object",
+                        "description": "Match nodes by property (used when node_id is omitted). Each value is either the value to match or an object of operators: =, !=, >, >=, <, <=, contains, exists, e.g. {\"year\": {\">=\": 2020}}"
+                    },
+                    "edge_properties": {
+                        "type": "object",
+                        "description": "Match edges by property, in the same form as node_properties (used when node_id is omitted)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Nodes plus edges per page for traversals and property matches, nodes first (capped by the server)"
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "nextCursor from a previous call, to fetch the following page"
                     }
                 }
             }),
         });
 
+        self.tools.push(Tool {
+            name: "mad_cypher_query".to_string(),
+            description: "Run a read-only Cypher query against the document's knowledge graph: MATCH patterns with labels, relationship types and variable-length paths (*1..3), WHERE, RETURN with aggregates, ORDER BY, SKIP and LIMIT".to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "e.g. MATCH (p:Person)-[:WROTE]->(d:Document) WHERE d.year >= 2020 RETURN p.name, count(d) AS docs ORDER BY docs DESC"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Rows per page (capped by the server)"
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "nextCursor from a previous call, to fetch the following page"
+                    }
+                },
+                "required": ["query"]
+            }),
+        });
+
         self.tools.push(Tool {
             name: "mad_export".to_string(),
             description: "Export document content in various formats".to_string(),
@@ -126,12 +371,12 @@ impl McpServer {
                 "properties": {
                     "format": {
                         "type": "string",
-                        "enum": ["html", "markdown", "json", "pdf"],
+                        "enum": ["html", "markdown", "json", "adam"],
                         "description": "Export format"
                     },
                     "include_metadata": {
                         "type": "boolean",
-                        "description": "Include document metadata",
+                        "description": "Include a document metadata header (html and markdown)",
                         "default": true
                     }
                 },
@@ -150,83 +395,155 @@ impl McpServer {
         });
     }
 
-    #[wasm_bindgen]
-    pub fn set_document(&mut self, document: crate::MadDocument) {
+    pub fn set_document(&mut self, document: MadDocument) {
         self.document = Some(document);
         self.update_resources();
     }
 
+    pub fn set_embedding_provider(&mut self, provider: Box<dyn EmbeddingProvider>) {
+        self.embedding_provider = Some(provider);
+    }
+
+    pub fn set_limits(&mut self, limits: McpLimits) {
+        self.limits = McpLimits {
+            max_results: limits.max_results.max(1),
+            max_graph_items: limits.max_graph_items.max(1),
+            list_page_size: limits.list_page_size.max(1),
+        };
+    }
+
+    pub fn limits(&self) -> McpLimits {
+        self.limits
+    }
+
+    pub fn tools(&self) -> &[Tool] {
+        &self.tools
+    }
+
     fn update_resources(&mut self) {
         self.resources.clear();
         if let Some(ref doc) = self.document {
-            let metadata = doc.get_metadata();
-            if let Ok(meta) = serde_json::from_str::<crate::DocumentMetadata>(&metadata) {
-                self.resources.push(Resource {
-                    uri: format!("mad://{}/content", meta.id),
-                    name: format!("{} - Content", meta.title),
-                    description: "Document content and media".to_string(),
-                    mime_type: "application/vnd.mad.content".to_string(),
-                });
+            let meta = doc.metadata();
+            self.resources.push(Resource {
+                uri: format!("mad://{}/content", meta.id),
+                name: format!("{} - Content", meta.title),
+                description: "Document content and media".to_string(),
+                mime_type: "application/vnd.mad.content+json".to_string(),
+            });
 
-                self.resources.push(Resource {
-                    uri: format!("mad://{}/vectors", meta.id),
-                    name: format!("{} - Vector Index", meta.title),
-                    description: "Vector embeddings for semantic search".to_string(),
-                    mime_type: "application/vnd.mad.vectors".to_string(),
-                });
+            self.resources.push(Resource {
+                uri: format!("mad://{}/vectors", meta.id),
+                name: format!("{} - Vector Index", meta.title),
+                description: "Vector embeddings for semantic search".to_string(),
+                mime_type: "application/vnd.mad.vectors+json".to_string(),
+            });
 
+            self.resources.push(Resource {
+                uri: format!("mad://{}/graph", meta.id),
+                name: format!("{} - Knowledge Graph", meta.title),
+                description: "Entity relationships and knowledge graph".to_string(),
+                mime_type: "application/vnd.mad.graph+json".to_string(),
+            });
+
+            for item in doc.content_items() {
                 self.resources.push(Resource {
-                    uri: format!("mad://{}/graph", meta.id),
-                    name: format!("{} - Knowledge Graph", meta.title),
-                    description: "Entity relationships and knowledge graph".to_string(),
-                    mime_type: "application/vnd.mad.graph".to_string(),
+                    uri: format!("mad://{}/content/{}", meta.id, item.id),
+                    name: format!("{} - {}", meta.title, content_label(item)),
+                    description: format!("Content item ({})", item.content_type),
+                    mime_type: item.content_type.clone(),
                 });
             }
         }
     }
 
-    #[wasm_bindgen]
+    fn require_document(&self) -> Result<&MadDocument, McpError> {
+        self.document.as_ref().ok_or_else(|| McpError {
+            code: -32000,
+            message: "No document loaded".to_string(),
+        })
+    }
+
     pub fn handle_request(&self, request_json: &str) -> String {
-        let request: McpRequest = match serde_json::from_str(request_json) {
+        // Notifications produce no response; callers get an empty string
+        self.handle_message(request_json).unwrap_or_default()
+    }
+
+    // Handle one JSON-RPC message (single request, notification or batch).
+    // Returns None when nothing should be written back.
+    pub fn handle_message(&self, message: &str) -> Option<String> {
+        let value: serde_json::Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(e) => {
+                let response = McpResponse::new(None, Err(McpError {
+                    code: -32700,
+                    message: format!("Parse error: {}", e),
+                }));
+                return serde_json::to_string(&response).ok();
+            }
+        };
+
+        match value {
+            serde_json::Value::Array(batch) => {
+                if batch.is_empty() {
+                    let response = McpResponse::new(None, Err(McpError {
+                        code: -32600,
+                        message: "Invalid Request: empty batch".to_string(),
+                    }));
+                    return serde_json::to_string(&response).ok();
+                }
+
+                let responses: Vec<McpResponse> = batch.into_iter()
+                    .filter_map(|entry| self.dispatch(entry))
+                    .collect();
+
+                if responses.is_empty() {
+                    None
+                } else {
+                    serde_json::to_string(&responses).ok()
+                }
+            }
+            single => self.dispatch(single)
+                .and_then(|response| serde_json::to_string(&response).ok()),
+        }
+    }
+
+    fn dispatch(&self, value: serde_json::Value) -> Option<McpResponse> {
+        let is_notification = value.is_object() && value.get("id").is_none();
+        let request: McpRequest = match serde_json::from_value(value.clone()) {
             Ok(req) => req,
             Err(e) => {
-                return serde_json::to_string(&McpResponse {
-                    result: None,
-                    error: Some(McpError {
-                        code: -32700,
-                        message: format!("Parse error: {}", e),
-                    }),
-                    id: None,
-                }).unwrap_or_default();
+                return Some(McpResponse::new(value.get("id").cloned(), Err(McpError {
+                    code: -32600,
+                    message: format!("Invalid Request: {}", e),
+                })));
             }
         };
+        if let Some(id) = value.get("id").filter(|id| !id.is_string() && !id.is_number()) {
+            return Some(McpResponse::new(Some(id.clone()), Err(McpError {
+                code: -32600,
+                message: "Invalid Request: id must be a string or a number".to_string(),
+            })));
+        }
 
         let result = match request.method.as_str() {
             "initialize" => self.handle_initialize(),
-            "tools/list" => self.handle_tools_list(),
+            "ping" => Ok(serde_json::json!({})),
+            "tools/list" => self.handle_tools_list(&request.params),
             "tools/call" => self.handle_tool_call(&request.params),
-            "resources/list" => self.handle_resources_list(),
+            "resources/list" => self.handle_resources_list(&request.params),
             "resources/read" => self.handle_resource_read(&request.params),
+            method if method.starts_with("notifications/") => Ok(serde_json::Value::Null),
             _ => Err(McpError {
                 code: -32601,
                 message: "Method not found".to_string(),
             }),
         };
 
-        let response = match result {
-            Ok(data) => McpResponse {
-                result: Some(data),
-                error: None,
-                id: request.id,
-            },
-            Err(error) => McpResponse {
-                result: None,
-                error: Some(error),
-                id: request.id,
-            },
-        };
+        if is_notification {
+            return None;
+        }
 
-        serde_json::to_string(&response).unwrap_or_default()
+        Some(McpResponse::new(request.id, result))
     }
 
     fn handle_initialize(&self) -> Result<serde_json::Value, McpError> {
@@ -243,10 +560,9 @@ impl McpServer {
         }))
     }
 
-    fn handle_tools_list(&self) -> Result<serde_json::Value, McpError> {
-        Ok(serde_json::json!({
-            "tools": self.tools
-        }))
+    fn handle_tools_list(&self, params: &HashMap<String, serde_json::Value>) -> Result<serde_json::Value, McpError> {
+        let page = self.list_page(&self.tools, params)?;
+        Ok(list_result("tools", page))
     }
 
     fn handle_tool_call(&self, params: &HashMap<String, serde_json::Value>) -> Result<serde_json::Value, McpError> {
@@ -263,17 +579,22 @@ impl McpServer {
                 message: "Missing arguments".to_string(),
             })?;
 
-        match tool_name {
+        // Failures inside a tool are part of its result, so the model sees
+        // them; JSON-RPC errors are kept for protocol problems
+        let result = match tool_name {
             "mad_search" => self.handle_search(arguments),
             "mad_vector_search" => self.handle_vector_search(arguments),
+            "mad_hybrid_search" => self.handle_hybrid_search(arguments),
             "mad_graph_query" => self.handle_graph_query(arguments),
+            "mad_cypher_query" => self.handle_cypher_query(arguments),
             "mad_export" => self.handle_export(arguments),
             "mad_metadata" => self.handle_metadata(),
-            _ => Err(McpError {
+            _ => return Err(McpError {
                 code: -32602,
                 message: "Unknown tool".to_string(),
             }),
-        }
+        };
+        Ok(result.unwrap_or_else(|error| tool_error(&error.message)))
     }
 
     fn handle_search(&self, args: &serde_json::Value) -> Result<serde_json::Value, McpError> {
@@ -284,76 +605,174 @@ impl McpServer {
                 message: "Missing query parameter".to_string(),
             })?;
 
-        if let Some(ref doc) = self.document {
-            match doc.search_content(query) {
-                Ok(results) => {
-                    let parsed: serde_json::Value = serde_json::from_str(&results)
-                        .unwrap_or_else(|_| serde_json::json!([]));
-                    Ok(serde_json::json!({
-                        "content": [{
-                            "type": "text",
-                            "text": format!("Search results for '{}': {}", query, results)
-                        }]
-                    }))
-                },
-                Err(e) => Err(McpError {
-                    code: -32000,
-                    message: format!("Search error: {:?}", e),
-                }),
-            }
-        } else {
-            Err(McpError {
-                code: -32000,
-                message: "No document loaded".to_string(),
-            })
-        }
+        let (offset, limit) = self.page_args(args, "limit", 10, self.limits.max_results)?;
+        let hits = self.require_document()?.search_text(query, offset.saturating_add(limit + 1));
+        tool_result(&SearchResults::new(Some(query), Page::slice(hits, offset, limit)))
     }
 
+    // An explicit embedding wins; a query alone is embedded with the document's model
     fn handle_vector_search(&self, args: &serde_json::Value) -> Result<serde_json::Value, McpError> {
-        let embedding = args.get("embedding")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| McpError {
+        let query = args.get("query").and_then(|v| v.as_str());
+        let embedding = embedding_arg(args)?;
+        let (offset, limit) = self.page_args(args, "top_k", 5, self.limits.max_results)?;
+
+        let options = VectorSearchOptions {
+            top_k: offset.saturating_add(limit + 1),
+            ef: args.get("ef").and_then(|v| v.as_u64()).map(|ef| ef as usize),
+            filter: filter_arg(args)?,
+        };
+
+        let document = self.require_document()?;
+        let hits = match (embedding, query) {
+            (Some(embedding), query) => document.vector_search(&embedding, &options, query)?,
+            (None, Some(query)) => document.semantic_search(query, &options, self.embedding_provider.as_deref())?,
+            (None, None) => return Err(McpError {
                 code: -32602,
-                message: "Missing or invalid embedding parameter".to_string(),
-            })?;
+                message: "Missing query or embedding parameter".to_string(),
+            }),
+        };
+        tool_result(&SearchResults::new(query, Page::slice(hits, offset, limit)))
+    }
 
-        let embedding_vec: Result<Vec<f32>, _> = embedding.iter()
-            .map(|v| v.as_f64().map(|f| f as f32))
-            .collect::<Option<Vec<_>>>()
+    fn handle_hybrid_search(&self, args: &serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let query = args.get("query")
+            .and_then(|v| v.as_str())
             .ok_or_else(|| McpError {
                 code: -32602,
-                message: "Invalid embedding format".to_string(),
-            });
+                message: "Missing query parameter".to_string(),
+            })?;
+        let embedding = embedding_arg(args)?;
+        let (offset, limit) = self.page_args(args, "top_k", 10, self.limits.max_results)?;
+
+        let number = |key: &str, default: f64| args.get(key).and_then(|v| v.as_f64()).unwrap_or(default) as f32;
+        let fusion = match args.get("fusion").and_then(|v| v.as_str()).unwrap_or("rrf") {
+            "rrf" => FusionMethod::Rrf { k: number("rrf_k", 60.0) },
+            "weighted" => FusionMethod::Weighted {
+                text_weight: number("text_weight", 0.5),
+                vector_weight: number("vector_weight", 0.5),
+            },
+            other => return Err(MadError::InvalidInput(format!("unknown fusion method '{}'", other)).into()),
+        };
+
+        let content_types = match args.get("content_type") {
+            Some(serde_json::Value::String(content_type)) => vec![content_type.clone()],
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| MadError::InvalidInput(format!("content_type: {}", e)))?,
+            None => Vec::new(),
+        };
+
+        let options = HybridSearchOptions {
+            top_k: offset.saturating_add(limit + 1),
+            fusion,
+            filter: SearchFilter {
+                content_types,
+                metadata: filter_arg(args)?,
+            },
+            ef: None,
+        };
+
+        let hits = self.require_document()?.hybrid_search(query, embedding.as_deref(), &options)?;
+        tool_result(&SearchResults::new(Some(query), Page::slice(hits, offset, limit)))
+    }
 
-        let embedding_vec = embedding_vec?;
-        let top_k = args.get("top_k")
+    fn handle_graph_query(&self, args: &serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let graph = self.require_document()?.graph();
+        let node_id = args.get("node_id").and_then(|v| v.as_str());
+        let target_node_id = args.get("target_node_id").and_then(|v| v.as_str());
+        let relationship = args.get("relationship").and_then(|v| v.as_str());
+        let depth = args.get("depth")
             .and_then(|v| v.as_u64())
-            .unwrap_or(5) as usize;
+            .unwrap_or(1) as usize;
 
-        if let Some(ref doc) = self.document {
-            let results = doc.vector_similarity_search(&embedding_vec, top_k);
-            Ok(serde_json::json!({
-                "content": [{
-                    "type": "text",
-                    "text": format!("Vector search results: {}", results)
-                }]
-            }))
-        } else {
-            Err(McpError {
-                code: -32000,
-                message: "No document loaded".to_string(),
-            })
+        let (offset, limit) = self.page_args(args, "limit", self.limits.max_graph_items, self.limits.max_graph_items)?;
+
+        let (mode, mut result) = match (node_id, target_node_id) {
+            (Some(start), Some(end)) => {
+                for id in [start, end] {
+                    if graph.node(id).is_none() {
+                        return Err(MadError::NotFound(format!("Node {}", id)).into());
+                    }
+                }
+                ("shortest_path", graph.shortest_path_result(start, end, relationship))
+            },
+            (Some(start), None) => {
+                if graph.node(start).is_none() {
+                    return Err(MadError::NotFound(format!("Node {}", start)).into());
+                }
+                ("traverse", graph.traverse_result(start, depth, relationship))
+            },
+            (None, Some(_)) => {
+                return Err(McpError {
+                    code: -32602,
+                    message: "target_node_id requires node_id".to_string(),
+                });
+            },
+            (None, None) => {
+                let node_filters = graph_filter(args, "node_properties")?;
+                let edge_filters = graph_filter(args, "edge_properties")?;
+                if node_filters.is_empty() && edge_filters.is_empty() {
+                    return Err(McpError {
+                        code: -32602,
+                        message: "Provide node_id or node_properties/edge_properties".to_string(),
+                    });
+                }
+                // An empty filter matches everything, so only report the side that was asked for
+                let mut result = graph.properties_query_result(&node_filters, &edge_filters);
+                if node_filters.is_empty() {
+                    result.nodes.clear();
+                }
+                if edge_filters.is_empty() {
+                    result.edges.clear();
+                }
+                ("properties", result)
+            },
+        };
+
+        // Counts are totals; a path is always returned whole. Otherwise nodes
+        // and edges are paged as one stream, nodes first: the cursor offset
+        // runs through the nodes and then on into the edges.
+        let node_count = result.nodes.len();
+        let edge_count = result.edges.len();
+        let mut next_cursor = None;
+        if mode != "shortest_path" {
+            let end = offset.saturating_add(limit);
+            let nodes = std::mem::take(&mut result.nodes);
+            let edges = std::mem::take(&mut result.edges);
+            result.nodes = nodes.into_iter().skip(offset).take(limit).collect();
+            result.edges = edges.into_iter()
+                .skip(offset.saturating_sub(node_count))
+                .take(limit - result.nodes.len())
+                .collect();
+            next_cursor = (node_count + edge_count > end).then(|| pagination::encode_cursor(end));
         }
+
+        tool_result(&GraphQueryPayload {
+            mode,
+            node_count,
+            edge_count,
+            path_found: mode != "shortest_path" || !result.paths.is_empty(),
+            result,
+            next_cursor,
+        })
     }
 
-    fn handle_graph_query(&self, _args: &serde_json::Value) -> Result<serde_json::Value, McpError> {
-        // Placeholder for graph query implementation
-        Ok(serde_json::json!({
-            "content": [{
-                "type": "text",
-                "text": "Graph query functionality not yet implemented"
-            }]
-        }))
+    fn handle_cypher_query(&self, args: &serde_json::Value) -> Result<serde_json::Value, McpError> {
+        let query = args.get("query")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| McpError {
+                code: -32602,
+                message: "Missing query parameter".to_string(),
+            })?;
+        let (offset, limit) = self.page_args(args, "limit", self.limits.max_results, self.limits.max_results)?;
+
+        let result = self.require_document()?.graph().cypher_result(query)?;
+        let page = Page::slice(result.rows, offset, limit);
+        tool_result(&CypherPayload {
+            columns: result.columns,
+            count: page.results.len(),
+            rows: page.results,
+            next_cursor: page.next_cursor,
+        })
     }
 
     fn handle_export(&self, args: &serde_json::Value) -> Result<serde_json::Value, McpError> {
@@ -364,51 +783,54 @@ impl McpServer {
                 message: "Missing format parameter".to_string(),
             })?;
 
-        if let Some(ref doc) = self.document {
-            let metadata = doc.get_metadata();
-            match format {
-                "json" => Ok(serde_json::json!({
-                    "content": [{
-                        "type": "text",
-                        "text": format!("Document metadata: {}", metadata)
-                    }]
-                })),
-                _ => Ok(serde_json::json!({
-                    "content": [{
-                        "type": "text",
-                        "text": format!("Export format '{}' not yet implemented", format)
-                    }]
-                })),
-            }
-        } else {
-            Err(McpError {
-                code: -32000,
-                message: "No document loaded".to_string(),
-            })
-        }
+        let include_metadata = args.get("include_metadata")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+
+        let doc = self.require_document()?;
+        let (mime_type, text) = match format {
+            "json" => ("application/json", doc.export_json()?),
+            "markdown" => ("text/markdown", doc.export_markdown(include_metadata)),
+            "html" => ("text/html", doc.export_html(include_metadata)),
+            "adam" => ("application/json", doc.to_adam_json()?),
+            _ => return Err(MadError::Unsupported(format!("export format '{}'", format)).into()),
+        };
+
+        Ok(serde_json::json!({
+            "content": [{
+                "type": "resource",
+                "resource": {
+                    "uri": format!("mad://{}/export.{}", doc.metadata().id, format),
+                    "mimeType": mime_type,
+                    "text": text
+                }
+            }]
+        }))
     }
 
     fn handle_metadata(&self) -> Result<serde_json::Value, McpError> {
-        if let Some(ref doc) = self.document {
-            let metadata = doc.get_metadata();
-            Ok(serde_json::json!({
-                "content": [{
-                    "type": "text",
-                    "text": metadata
-                }]
-            }))
-        } else {
-            Err(McpError {
-                code: -32000,
-                message: "No document loaded".to_string(),
-            })
-        }
+        tool_result(self.require_document()?.metadata())
     }
 
-    fn handle_resources_list(&self) -> Result<serde_json::Value, McpError> {
-        Ok(serde_json::json!({
-            "resources": self.resources
-        }))
+    fn handle_resources_list(&self, params: &HashMap<String, serde_json::Value>) -> Result<serde_json::Value, McpError> {
+        let page = self.list_page(&self.resources, params)?;
+        Ok(list_result("resources", page))
+    }
+
+    fn list_page<'a, T>(&self, items: &'a [T], params: &HashMap<String, serde_json::Value>) -> Result<Page<&'a T>, McpError> {
+        let offset = pagination::offset(params.get("cursor").and_then(|v| v.as_str()))?;
+        Ok(Page::slice(items.iter().collect(), offset, self.limits.list_page_size))
+    }
+
+    // (offset, page size) from the `cursor` argument and `key`, capped at `max`
+    fn page_args(&self, args: &serde_json::Value, key: &str, default: usize, max: usize) -> Result<(usize, usize), McpError> {
+        let offset = pagination::offset(args.get("cursor").and_then(|v| v.as_str()))?;
+        let limit = args.get(key)
+            .and_then(|v| v.as_u64())
+            .map(|limit| limit as usize)
+            .unwrap_or(default)
+            .clamp(1, max);
+        Ok((offset, limit))
     }
 
     fn handle_resource_read(&self, params: &HashMap<String, serde_json::Value>) -> Result<serde_json::Value, McpError> {
@@ -419,23 +841,338 @@ impl McpServer {
                 message: "Missing URI parameter".to_string(),
             })?;
 
-        // Parse the MAD URI and return appropriate content
+        let doc = self.require_document()?;
+        let meta = doc.metadata();
+
+        // mad://{document_id}/{content|vectors|graph}[/{item_id}]
+        let path = uri.strip_prefix("mad://")
+            .ok_or_else(|| MadError::InvalidInput(format!("Not a mad:// URI: {}", uri)))?;
+        let mut parts = path.splitn(3, '/');
+        let doc_id = parts.next().unwrap_or_default();
+        let kind = parts.next().unwrap_or_default();
+        let item_id = parts.next();
+
+        if doc_id != meta.id {
+            return Err(resource_not_found(uri));
+        }
+
+        let content = match (kind, item_id) {
+            ("content", None) => {
+                let items: Vec<serde_json::Value> = doc.content_items().iter().map(|item| {
+                    serde_json::json!({
+                        "id": item.id,
+                        "uri": format!("mad://{}/content/{}", meta.id, item.id),
+                        "content_type": item.content_type,
+                        "text_content": item.text_content,
+                        "metadata": item.metadata,
+                        "size_bytes": decoded_data(item).len()
+                    })
+                }).collect();
+                json_resource(uri, "application/vnd.mad.content+json", &serde_json::json!({
+                    "document_id": meta.id,
+                    "items": items
+                }))
+            },
+            ("content", Some(id)) => {
+                let item = doc.content_item(id)
+                    .ok_or_else(|| resource_not_found(uri))?;
+                item_resource(uri, item)
+            },
+            ("vectors", None) => {
+                let mut dimensions: Vec<usize> = doc.vectors().iter().map(|v| v.dimension()).collect();
+                dimensions.sort_unstable();
+                dimensions.dedup();
+                let entries: Vec<serde_json::Value> = doc.vectors().iter().map(|v| {
+                    serde_json::json!({
+                        "id": v.id,
+                        "dimension": v.dimension(),
+                        "content_preview": v.content.chars().take(200).collect::<String>(),
+                        "metadata": v.metadata
+                    })
+                }).collect();
+                json_resource(uri, "application/vnd.mad.vectors+json", &serde_json::json!({
+                    "document_id": meta.id,
+                    "count": entries.len(),
+                    "dimensions": dimensions,
+                    "vectors": entries
+                }))
+            },
+            ("graph", None) => {
+                let graph: serde_json::Value = serde_json::from_str(&doc.graph().export_json())
+                    .map_err(MadError::from)?;
+                json_resource(uri, "application/vnd.mad.graph+json", &graph)
+            },
+            _ => return Err(resource_not_found(uri)),
+        };
+
         Ok(serde_json::json!({
-            "contents": [{
-                "uri": uri,
-                "mimeType": "text/plain",
-                "text": format!("Content for resource: {}", uri)
-            }]
+            "contents": [content]
         }))
     }
 
-    #[wasm_bindgen]
     pub fn get_tools_json(&self) -> String {
         serde_json::to_string(&self.tools).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
     pub fn get_resources_json(&self) -> String {
         serde_json::to_string(&self.resources).unwrap_or_default()
     }
-}
\ No newline at end of file
+}
+
+// Tool output as structuredContent, repeated as JSON text for clients that
+// only read `content`
+fn tool_result<T: Serialize>(payload: &T) -> Result<serde_json::Value, McpError> {
+    let structured = serde_json::to_value(payload).map_err(MadError::from)?;
+    let text = serde_json::to_string_pretty(&structured).map_err(MadError::from)?;
+    Ok(serde_json::json!({
+        "content": [{
+            "type": "text",
+            "text": text
+        }],
+        "structuredContent": structured
+    }))
+}
+
+fn tool_error(message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "content": [{
+            "type": "text",
+            "text": message
+        }],
+        "isError": true
+    })
+}
+
+fn resource_not_found(uri: &str) -> McpError {
+    McpError {
+        code: -32002,
+        message: MadError::NotFound(format!("Resource {}", uri)).to_string(),
+    }
+}
+
+// `{key: [...], "nextCursor"}`, leaving the cursor out on the last page
+fn list_result<T: Serialize>(key: &str, page: Page<T>) -> serde_json::Value {
+    let mut result = serde_json::json!({ key: page.results });
+    if let Some(cursor) = page.next_cursor {
+        result["nextCursor"] = serde_json::Value::String(cursor);
+    }
+    result
+}
+
+fn json_resource(uri: &str, mime_type: &str, value: &serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "uri": uri,
+        "mimeType": mime_type,
+        "text": serde_json::to_string_pretty(value).unwrap_or_default()
+    })
+}
+
+// Text-like items come back as text, everything else as the stored base64 blob
+fn item_resource(uri: &str, item: &ContentItem) -> serde_json::Value {
+    let is_text = item.content_type.starts_with("text/")
+        || item.content_type == "application/json"
+        || item.content_type.ends_with("+json")
+        || item.content_type.ends_with("xml");
+
+    if is_text {
+        let data = decoded_data(item);
+        let text = match String::from_utf8(data) {
+            Ok(text) if !text.is_empty() => text,
+            _ => item.text_content.clone(),
+        };
+        serde_json::json!({
+            "uri": uri,
+            "mimeType": item.content_type,
+            "text": text
+        })
+    } else {
+        serde_json::json!({
+            "uri": uri,
+            "mimeType": item.content_type,
+            "blob": item.data
+        })
+    }
+}
+
+fn decoded_data(item: &ContentItem) -> Vec<u8> {
+    BASE64.decode(&item.data).unwrap_or_default()
+}
+
+// Short human-readable name for an item: its first line of text, else its ID
+fn content_label(item: &ContentItem) -> String {
+    let first_line = item.text_content.lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or(&item.id);
+
+    if first_line.chars().count() > 60 {
+        format!("{}...", first_line.chars().take(60).collect::<String>())
+    } else {
+        first_line.to_string()
+    }
+}
+
+// Optional "embedding" argument; present but malformed is an error
+fn embedding_arg(args: &serde_json::Value) -> Result<Option<Vec<f32>>, McpError> {
+    let Some(embedding) = args.get("embedding") else {
+        return Ok(None);
+    };
+
+    embedding.as_array()
+        .and_then(|values| values.iter().map(|v| v.as_f64().map(|f| f as f32)).collect::<Option<Vec<_>>>())
+        .map(Some)
+        .ok_or_else(|| McpError {
+            code: -32602,
+            message: "Invalid embedding format".to_string(),
+        })
+}
+
+// The `filter` expression shared by mad_vector_search and mad_hybrid_search
+fn filter_arg(args: &serde_json::Value) -> Result<Option<MetadataFilter>, McpError> {
+    Ok(args.get("filter")
+        .and_then(|v| v.as_str())
+        .map(MetadataFilter::parse)
+        .transpose()?)
+}
+
+fn graph_filter(args: &serde_json::Value, key: &str) -> Result<PropertyFilter, McpError> {
+    match args.get(key) {
+        Some(value) => PropertyFilter::from_value(value).map_err(|e| match e {
+            MadError::InvalidInput(message) => MadError::InvalidInput(format!("{}: {}", key, message)).into(),
+            e => e.into(),
+        }),
+        None => Ok(PropertyFilter::default()),
+    }
+}
+
+impl Default for McpServer {
+    fn default() -> Self {
+        McpServer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn server() -> McpServer {
+        let mut doc = MadDocument::new("Notes", "Ada");
+        let intro = doc.add_content("text/plain", b"", "graph databases and vector search").unwrap();
+        let table = doc.add_content("text/markdown", b"", "a table of vector results").unwrap();
+        doc.add_vector_embedding(&intro, "intro", &[1.0, 0.0]).unwrap();
+        doc.add_vector_embedding(&table, "table", &[0.0, 1.0]).unwrap();
+        doc.add_graph_node("a", "Topic", r#"{"rank": 1}"#).unwrap();
+        doc.add_graph_node("b", "Topic", r#"{"rank": 2}"#).unwrap();
+        doc.add_graph_edge("a", "b", "RELATED_TO", "").unwrap();
+
+        let mut server = McpServer::new();
+        server.set_document(doc);
+        server
+    }
+
+    fn request(server: &McpServer, method: &str, params: Value) -> Value {
+        let message = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        serde_json::from_str(&server.handle_request(&message.to_string())).unwrap()
+    }
+
+    fn call(server: &McpServer, tool: &str, arguments: Value) -> Value {
+        request(server, "tools/call", json!({"name": tool, "arguments": arguments}))["result"].clone()
+    }
+
+    #[test]
+    fn dispatches_protocol_messages() {
+        let server = server();
+        assert_eq!(request(&server, "initialize", json!({}))["result"]["serverInfo"]["name"], "mad-server");
+        assert_eq!(request(&server, "ping", json!({}))["result"], json!({}));
+        assert_eq!(request(&server, "nope", json!({}))["error"]["code"], -32601);
+
+        let parse_error: Value = serde_json::from_str(&server.handle_request("{not json")).unwrap();
+        assert_eq!(parse_error["error"]["code"], -32700);
+
+        assert!(server.handle_message(r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#).is_none());
+        let batch = server.handle_message(r#"[{"jsonrpc": "2.0", "id": 1, "method": "ping"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"}]"#).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&batch).unwrap().as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn null_ids_are_invalid_requests() {
+        let server = server();
+        for id in ["null", "{}", "[1]"] {
+            let message = format!(r#"{{"jsonrpc": "2.0", "id": {}, "method": "ping"}}"#, id);
+            let response: Value = serde_json::from_str(&server.handle_message(&message).unwrap()).unwrap();
+            assert_eq!(response["error"]["code"], -32600, "{}", id);
+            assert!(response.get("result").is_none());
+            assert_eq!(response["id"], serde_json::from_str::<Value>(id).unwrap());
+        }
+    }
+
+    #[test]
+    fn vector_and_hybrid_search_share_filters() {
+        let server = server();
+        let filter = "content_type = 'text/markdown'";
+        for (tool, arguments) in [
+            ("mad_vector_search", json!({"embedding": [1.0, 0.0], "filter": filter})),
+            ("mad_hybrid_search", json!({"query": "vector", "embedding": [1.0, 0.0], "filter": filter})),
+        ] {
+            let results = call(&server, tool, arguments)["structuredContent"]["results"].clone();
+            assert_eq!(results.as_array().unwrap().len(), 1, "{}", tool);
+            assert_eq!(results[0]["content_type"], "text/markdown", "{}", tool);
+        }
+    }
+
+    #[test]
+    fn tool_failures_are_results_and_protocol_errors_are_not() {
+        let server = server();
+        let missing_query = call(&server, "mad_search", json!({}));
+        assert_eq!(missing_query["isError"], true);
+        assert!(missing_query["content"][0]["text"].as_str().unwrap().contains("query"));
+        let mismatch = call(&server, "mad_vector_search", json!({"embedding": [1.0, 0.0, 0.0]}));
+        assert_eq!(mismatch["isError"], true);
+
+        let unknown_tool = request(&server, "tools/call", json!({"name": "mad_nope", "arguments": {}}));
+        assert_eq!(unknown_tool["error"]["code"], -32602);
+
+        let missing = request(&server, "resources/read", json!({"uri": "mad://other/content"}));
+        assert_eq!(missing["error"]["code"], -32002);
+        let no_document = McpServer::new();
+        assert_eq!(call(&no_document, "mad_metadata", json!({}))["isError"], true);
+    }
+
+    #[test]
+    fn tool_results_repeat_structured_content_as_text() {
+        let result = call(&server(), "mad_search", json!({"query": "vector"}));
+        assert_eq!(result["structuredContent"]["count"], 2);
+        let text: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(text, result["structuredContent"]);
+    }
+
+    #[test]
+    fn pages_graph_results_and_lists() {
+        let mut server = server();
+        server.set_limits(McpLimits { max_results: 50, max_graph_items: 2, list_page_size: 3 });
+
+        let mut items = Vec::new();
+        // A condition on an unused key matches every edge
+        let mut arguments = json!({"node_properties": {"rank": {">": 0}}, "edge_properties": {"unused": {"exists": false}}});
+        loop {
+            let page = call(&server, "mad_graph_query", arguments.clone())["structuredContent"].clone();
+            let result = &page["result"];
+            items.extend(result["nodes"].as_array().unwrap().iter().map(|n| n["id"].clone()));
+            items.extend(result["edges"].as_array().unwrap().iter().map(|e| e["relationship"].clone()));
+            match page["nextCursor"].as_str() {
+                Some(cursor) => arguments["cursor"] = json!(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(items, vec![json!("a"), json!("b"), json!("RELATED_TO")]);
+
+        let first = request(&server, "tools/list", json!({}))["result"].clone();
+        assert_eq!(first["tools"].as_array().unwrap().len(), 3);
+        let cursor = first["nextCursor"].clone();
+        let second = request(&server, "tools/list", json!({"cursor": cursor}))["result"].clone();
+        assert_ne!(second["tools"][0]["name"], first["tools"][0]["name"]);
+        assert_eq!(request(&server, "tools/list", json!({"cursor": "bogus"}))["error"]["code"], -32602);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::error::{MadError, MadResult};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64};
+use serde::{Deserialize, Serialize};
+
+// One page of a longer result list. `next_cursor` is opaque to callers and is
+// passed back unchanged to fetch the following page.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Page<T> {
+    pub results: Vec<T>,
+    #[serde(rename = "nextCursor", default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    // `items` holds everything from the start of the list, or at least
+    // `offset + limit + 1` entries so the presence of a next page is known
+    pub fn slice(items: Vec<T>, offset: usize, limit: usize) -> Page<T> {
+        let has_more = items.len() > offset.saturating_add(limit);
+        let results: Vec<T> = items.into_iter().skip(offset).take(limit).collect();
+        Page {
+            results,
+            next_cursor: has_more.then(|| encode_cursor(offset + limit)),
+        }
+    }
+}
+
+// Cursors encode the offset of the page's first item
+pub fn encode_cursor(offset: usize) -> String {
+    BASE64.encode(format!("offset:{}", offset))
+}
+
+pub fn decode_cursor(cursor: &str) -> MadResult<usize> {
+    BASE64.decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|text| text.strip_prefix("offset:").and_then(|n| n.parse().ok()))
+        .ok_or_else(|| MadError::InvalidInput(format!("Invalid cursor '{}'", cursor)))
+}
+
+// Offset for an optional cursor; no cursor means the first page
+pub fn offset(cursor: Option<&str>) -> MadResult<usize> {
+    cursor.map(decode_cursor).transpose().map(|offset| offset.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_pages_with_cursors() {
+        let first = Page::slice((0..5).collect(), 0, 2);
+        assert_eq!(first.results, vec![0, 1]);
+        let offset = offset(first.next_cursor.as_deref()).unwrap();
+        assert_eq!(Page::slice((0..5).collect(), offset, 2).results, vec![2, 3]);
+
+        let last = Page::slice((0..5).collect(), 4, 2);
+        assert_eq!(last.results, vec![4]);
+        assert!(last.next_cursor.is_none());
+    }
+
+    #[test]
+    fn rejects_foreign_cursors() {
+        assert_eq!(offset(None).unwrap(), 0);
+        assert_eq!(decode_cursor(&encode_cursor(42)).unwrap(), 42);
+        assert!(decode_cursor("not-a-cursor").is_err());
+        assert!(decode_cursor(&BASE64.encode("offset:-1")).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::{MadError, MadResult};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantization {
+    // One byte per dimension, scaled between the vector's min and max
+    Int8,
+    // One bit per dimension (its sign)
+    Binary,
+}
+
+impl Quantization {
+    pub fn from_name(name: &str) -> MadResult<Quantization> {
+        match name.to_ascii_lowercase().as_str() {
+            "int8" => Ok(Quantization::Int8),
+            "binary" => Ok(Quantization::Binary),
+            _ => Err(MadError::InvalidInput(format!("Unknown quantization '{}'", name))),
+        }
+    }
+}
+
+// Compact form of an embedding, stored base64-encoded in vectors.json
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QuantizedVector {
+    pub method: Quantization,
+    pub dimension: usize,
+    // Int8 only: value = min + byte * step
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub min: f32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub step: f32,
+    #[serde(serialize_with = "to_base64", deserialize_with = "from_base64")]
+    pub data: Vec<u8>,
+}
+
+impl QuantizedVector {
+    pub fn encode(vector: &[f32], method: Quantization) -> QuantizedVector {
+        match method {
+            Quantization::Int8 => {
+                let min = vector.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = vector.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                let step = if max > min { (max - min) / 255.0 } else { 0.0 };
+                let data = vector.iter()
+                    .map(|x| if step > 0.0 { ((x - min) / step).round() as u8 } else { 0 })
+                    .collect();
+                let min = if vector.is_empty() { 0.0 } else { min };
+                QuantizedVector { method, dimension: vector.len(), min, step, data }
+            }
+            Quantization::Binary => {
+                let mut data = vec![0u8; vector.len().div_ceil(8)];
+                for (i, x) in vector.iter().enumerate() {
+                    if *x > 0.0 {
+                        data[i / 8] |= 0x80 >> (i % 8);
+                    }
+                }
+                QuantizedVector { method, dimension: vector.len(), min: 0.0, step: 0.0, data }
+            }
+        }
+    }
+
+    // Approximate reconstruction; binary vectors decode to +1 / -1 per dimension
+    pub fn decode(&self) -> Vec<f32> {
+        match self.method {
+            Quantization::Int8 => self.data.iter().map(|&b| self.min + b as f32 * self.step).collect(),
+            Quantization::Binary => (0..self.dimension)
+                .map(|i| if self.data[i / 8] & (0x80 >> (i % 8)) != 0 { 1.0 } else { -1.0 })
+                .collect(),
+        }
+    }
+
+    pub fn check(&self) -> MadResult<()> {
+        let expected = match self.method {
+            Quantization::Int8 => self.dimension,
+            Quantization::Binary => self.dimension.div_ceil(8),
+        };
+        if self.data.len() != expected {
+            return Err(MadError::IntegrityFailure(format!(
+                "quantized vector holds {} bytes, expected {} for dimension {}",
+                self.data.len(),
+                expected,
+                self.dimension
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn is_zero(value: &f32) -> bool {
+    *value == 0.0
+}
+
+fn to_base64<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE64.encode(data))
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    BASE64.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int8_decodes_within_one_step() {
+        let vector = [-1.0, -0.25, 0.0, 0.3, 2.0];
+        let quantized = QuantizedVector::encode(&vector, Quantization::Int8);
+        quantized.check().unwrap();
+        for (original, decoded) in vector.iter().zip(quantized.decode()) {
+            assert!((original - decoded).abs() <= quantized.step);
+        }
+    }
+
+    #[test]
+    fn binary_keeps_signs() {
+        let vector = [0.5, -0.1, 0.0, 3.0, -2.0, 1.0, 1.0, -1.0, 0.2];
+        let quantized = QuantizedVector::encode(&vector, Quantization::Binary);
+        assert_eq!(quantized.data.len(), 2);
+        assert_eq!(quantized.decode(), vec![1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0]);
+    }
+
+    #[test]
+    fn serializes_as_base64_and_checks_length() {
+        let quantized = QuantizedVector::encode(&[1.0, 2.0, 3.0], Quantization::Int8);
+        let json = serde_json::to_value(&quantized).unwrap();
+        assert!(json["data"].is_string());
+        assert_eq!(serde_json::from_value::<QuantizedVector>(json.clone()).unwrap(), quantized);
+
+        let mut short = json;
+        short["dimension"] = serde_json::json!(4);
+        let short: QuantizedVector = serde_json::from_value(short).unwrap();
+        assert!(matches!(short.check(), Err(MadError::IntegrityFailure(_))));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{ContentItem, MetadataFilter};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// How hybrid_search combines the lexical and semantic rankings
+#[derive(Clone, Copy, Debug)]
+pub enum FusionMethod {
+    // Reciprocal rank fusion: sum of 1 / (k + rank) over both rankings
+    Rrf { k: f32 },
+    // Min-max normalized scores, combined with the given weights
+    Weighted { text_weight: f32, vector_weight: f32 },
+}
+
+impl Default for FusionMethod {
+    fn default() -> Self {
+        FusionMethod::Rrf { k: 60.0 }
+    }
+}
+
+// Restricts search results to content items with one of the given content
+// types whose metadata matches the filter expression. As in vector search,
+// the expression can also test `content_type`.
+#[derive(Clone, Debug, Default)]
+pub struct SearchFilter {
+    pub content_types: Vec<String>,
+    pub metadata: Option<MetadataFilter>,
+}
+
+impl SearchFilter {
+    pub fn is_empty(&self) -> bool {
+        self.content_types.is_empty() && self.metadata.is_none()
+    }
+
+    pub fn matches(&self, item: &ContentItem) -> bool {
+        (self.content_types.is_empty() || self.content_types.contains(&item.content_type))
+            && self.metadata.as_ref().is_none_or(|filter| {
+                let mut metadata = item.metadata.clone();
+                metadata.insert("content_type".to_string(), item.content_type.clone());
+                filter.matches(&metadata)
+            })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HybridSearchOptions {
+    pub top_k: usize,
+    pub fusion: FusionMethod,
+    pub filter: SearchFilter,
+    // HNSW search breadth for the vector side (None = index default)
+    pub ef: Option<usize>,
+}
+
+impl Default for HybridSearchOptions {
+    fn default() -> Self {
+        HybridSearchOptions {
+            top_k: 10,
+            fusion: FusionMethod::default(),
+            filter: SearchFilter::default(),
+            ef: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HybridSearchHit {
+    pub id: String,
+    pub content_type: String,
+    pub score: f32,
+    // 1-based rank and raw score from each retriever that found the item
+    pub text_rank: Option<usize>,
+    pub text_score: Option<f32>,
+    pub vector_rank: Option<usize>,
+    pub vector_score: Option<f32>,
+    pub snippet: String,
+}
+
+// Entry in vector_similarity_search's JSON array
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VectorMatch {
+    pub id: String,
+    pub content: String,
+    pub score: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct VectorSearchOptions {
+    pub top_k: usize,
+    // HNSW search breadth (None = index default)
+    pub ef: Option<usize>,
+    // Applied before ranking
+    pub filter: Option<MetadataFilter>,
+}
+
+impl Default for VectorSearchOptions {
+    fn default() -> Self {
+        VectorSearchOptions {
+            top_k: 5,
+            ef: None,
+            filter: None,
+        }
+    }
+}
+
+// A ranked passage. Chunks also report where they sit in their parent item.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VectorSearchHit {
+    pub id: String,
+    pub content_type: String,
+    pub score: f32,
+    pub excerpt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_start: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_end: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading: Option<String>,
+}
+
+// Fuse two best-first rankings of (content ID, score) into one
+pub fn fuse(text: &[(String, f32)], vector: &[(String, f32)], method: FusionMethod) -> Vec<(String, f32)> {
+    let mut fused: HashMap<&str, f32> = HashMap::new();
+
+    match method {
+        FusionMethod::Rrf { k } => {
+            for ranking in [text, vector] {
+                for (rank, (id, _)) in ranking.iter().enumerate() {
+                    *fused.entry(id).or_default() += 1.0 / (k + rank as f32 + 1.0);
+                }
+            }
+        }
+        FusionMethod::Weighted { text_weight, vector_weight } => {
+            for (ranking, weight) in [(text, text_weight), (vector, vector_weight)] {
+                let max = ranking.iter().map(|(_, s)| *s).fold(f32::NEG_INFINITY, f32::max);
+                let min = ranking.iter().map(|(_, s)| *s).fold(f32::INFINITY, f32::min);
+                for (id, score) in ranking {
+                    let normalized = if max > min { (score - min) / (max - min) } else { 1.0 };
+                    *fused.entry(id).or_default() += weight * normalized;
+                }
+            }
+        }
+    }
+
+    let mut results: Vec<(String, f32)> = fused.into_iter()
+        .map(|(id, score)| (id.to_string(), score))
+        .collect();
+    results.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    results
+}
@@ -1,6 +1,7 @@
-use wasm_bindgen::prelude::*;
+use crate::{MadError, MadResult, SharedClock};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
 #[derive(Serialize, Deserialize)]
 pub struct MadRegistry {
@@ -52,17 +53,19 @@ pub struct DocumentSync {
     pub sync_status: String, // "up-to-date", "needs-pull", "needs-push", "conflict"
 }
 
-#[wasm_bindgen]
 pub struct SharingManager {
     registry: MadRegistry,
     local_peer_id: String,
     storage_root: String,
+    clock: SharedClock,
 }
 
-#[wasm_bindgen]
 impl SharingManager {
-    #[wasm_bindgen(constructor)]
     pub fn new(peer_id: &str, storage_root: &str) -> SharingManager {
+        SharingManager::with_clock(peer_id, storage_root, crate::clock::system_clock())
+    }
+
+    pub fn with_clock(peer_id: &str, storage_root: &str, clock: SharedClock) -> SharingManager {
         SharingManager {
             registry: MadRegistry {
                 documents: HashMap::new(),
@@ -70,18 +73,18 @@ impl SharingManager {
             },
             local_peer_id: peer_id.to_string(),
             storage_root: storage_root.to_string(),
+            clock,
         }
     }
 
-    #[wasm_bindgen]
-    pub fn register_document(&mut self, hash: &str, title: &str, author: &str, size_bytes: u64) -> Result<(), JsValue> {
+    pub fn register_document(&mut self, hash: &str, title: &str, author: &str, size_bytes: u64) -> MadResult<()> {
         let entry = DocumentEntry {
             hash: hash.to_string(),
             title: title.to_string(),
             author: author.to_string(),
             size_bytes,
-            created: js_sys::Date::now() as u64,
-            last_accessed: js_sys::Date::now() as u64,
+            created: self.clock.now_millis(),
+            last_accessed: self.clock.now_millis(),
             access_count: 0,
             availability: vec![self.local_peer_id.clone()],
         };
@@ -90,12 +93,11 @@ impl SharingManager {
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn register_peer(&mut self, peer_id: &str, address: &str) -> Result<(), JsValue> {
+    pub fn register_peer(&mut self, peer_id: &str, address: &str) -> MadResult<()> {
         let peer = PeerInfo {
             id: peer_id.to_string(),
             address: address.to_string(),
-            last_seen: js_sys::Date::now() as u64,
+            last_seen: self.clock.now_millis(),
             documents: Vec::new(),
             reliability_score: 1.0,
         };
@@ -104,10 +106,9 @@ impl SharingManager {
         Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn create_share_link(&self, document_hash: &str, access_level: &str, expiration_hours: Option<u32>) -> Result<String, JsValue> {
+    pub fn create_share_link(&self, document_hash: &str, access_level: &str, expiration_hours: Option<u32>) -> MadResult<String> {
         let expiration = expiration_hours.map(|hours| {
-            js_sys::Date::now() as u64 + (hours as u64 * 60 * 60 * 1000)
+            self.clock.now_millis() + (hours as u64 * 60 * 60 * 1000)
         });
 
         let share_request = ShareRequest {
@@ -118,43 +119,38 @@ impl SharingManager {
         };
 
         // Create a shareable link format
-        let share_data = serde_json::to_string(&share_request)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let share_data = serde_json::to_string(&share_request)?;
         
-        let encoded_data = base64::encode(share_data);
+        let encoded_data = BASE64.encode(share_data);
         Ok(format!("mad://share/{}", encoded_data))
     }
 
-    #[wasm_bindgen]
-    pub fn parse_share_link(&self, share_link: &str) -> Result<String, JsValue> {
+    pub fn parse_share_link(&self, share_link: &str) -> MadResult<String> {
         if !share_link.starts_with("mad://share/") {
-            return Err(JsValue::from_str("Invalid MAD share link"));
+            return Err(MadError::InvalidInput("Invalid MAD share link".to_string()));
         }
 
         let encoded_data = share_link.strip_prefix("mad://share/")
-            .ok_or_else(|| JsValue::from_str("Invalid share link format"))?;
+            .ok_or_else(|| MadError::InvalidInput("Invalid share link format".to_string()))?;
 
-        let decoded_data = base64::decode(encoded_data)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let decoded_data = BASE64.decode(encoded_data)
+            .map_err(|e| MadError::InvalidInput(e.to_string()))?;
 
         let share_data = String::from_utf8(decoded_data)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            .map_err(|e| MadError::InvalidInput(e.to_string()))?;
 
-        let share_request: ShareRequest = serde_json::from_str(&share_data)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let share_request: ShareRequest = serde_json::from_str(&share_data)?;
 
         // Check if link has expired
         if let Some(exp) = share_request.expiration {
-            if js_sys::Date::now() as u64 > exp {
-                return Err(JsValue::from_str("Share link has expired"));
+            if self.clock.now_millis() > exp {
+                return Err(MadError::Expired("Share link".to_string()));
             }
         }
 
-        Ok(serde_json::to_string(&share_request)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?)
+        Ok(serde_json::to_string(&share_request)?)
     }
 
-    #[wasm_bindgen]
     pub fn find_document_peers(&self, document_hash: &str) -> String {
         if let Some(doc_entry) = self.registry.documents.get(document_hash) {
             let peer_info: Vec<&PeerInfo> = doc_entry.availability.iter()
@@ -167,7 +163,6 @@ impl SharingManager {
         }
     }
 
-    #[wasm_bindgen]
     pub fn create_sync_manifest(&self) -> String {
         let document_syncs: Vec<DocumentSync> = self.registry.documents.iter()
             .map(|(hash, entry)| DocumentSync {
@@ -180,24 +175,22 @@ impl SharingManager {
 
         let manifest = SyncManifest {
             documents: document_syncs,
-            last_sync: js_sys::Date::now() as u64,
+            last_sync: self.clock.now_millis(),
             sync_version: 1,
         };
 
         serde_json::to_string(&manifest).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
     pub fn generate_ipfs_hash(&self, document_hash: &str) -> String {
         // In a real implementation, this would interface with IPFS
         // For now, we'll create a mock IPFS hash based on the MAD hash
         format!("Qm{}", hex::encode(&document_hash.as_bytes()[..20]))
     }
 
-    #[wasm_bindgen]
-    pub fn create_torrent_info(&self, document_hash: &str) -> Result<String, JsValue> {
+    pub fn create_torrent_info(&self, document_hash: &str) -> MadResult<String> {
         let doc_entry = self.registry.documents.get(document_hash)
-            .ok_or_else(|| JsValue::from_str("Document not found"))?;
+            .ok_or_else(|| MadError::NotFound(format!("Document {}", document_hash)))?;
 
         let torrent_info = serde_json::json!({
             "info_hash": document_hash,
@@ -222,37 +215,34 @@ impl SharingManager {
         Ok(torrent_info.to_string())
     }
 
-    #[wasm_bindgen]
-    pub fn get_availability_stats(&self, document_hash: &str) -> String {
-        if let Some(doc_entry) = self.registry.documents.get(document_hash) {
-            let available_peers = doc_entry.availability.len();
-            let total_peers = self.registry.peers.len();
-            let availability_ratio = if total_peers > 0 {
-                available_peers as f32 / total_peers as f32
-            } else {
-                0.0
-            };
-
-            let stats = serde_json::json!({
-                "document_hash": document_hash,
-                "available_peers": available_peers,
-                "total_peers": total_peers,
-                "availability_ratio": availability_ratio,
-                "access_count": doc_entry.access_count,
-                "last_accessed": doc_entry.last_accessed,
-                "redundancy_level": if available_peers >= 3 { "high" } else if available_peers >= 2 { "medium" } else { "low" }
-            });
-
-            stats.to_string()
+    pub fn get_availability_stats(&self, document_hash: &str) -> MadResult<String> {
+        let doc_entry = self.registry.documents.get(document_hash)
+            .ok_or_else(|| MadError::NotFound(format!("Document {}", document_hash)))?;
+
+        let available_peers = doc_entry.availability.len();
+        let total_peers = self.registry.peers.len();
+        let availability_ratio = if total_peers > 0 {
+            available_peers as f32 / total_peers as f32
         } else {
-            serde_json::json!({"error": "Document not found"}).to_string()
-        }
+            0.0
+        };
+
+        let stats = serde_json::json!({
+            "document_hash": document_hash,
+            "available_peers": available_peers,
+            "total_peers": total_peers,
+            "availability_ratio": availability_ratio,
+            "access_count": doc_entry.access_count,
+            "last_accessed": doc_entry.last_accessed,
+            "redundancy_level": if available_peers >= 3 { "high" } else if available_peers >= 2 { "medium" } else { "low" }
+        });
+
+        Ok(stats.to_string())
     }
 
-    #[wasm_bindgen]
-    pub fn export_portable_package(&self, document_hash: &str) -> Result<String, JsValue> {
+    pub fn export_portable_package(&self, document_hash: &str) -> MadResult<String> {
         let doc_entry = self.registry.documents.get(document_hash)
-            .ok_or_else(|| JsValue::from_str("Document not found"))?;
+            .ok_or_else(|| MadError::NotFound(format!("Document {}", document_hash)))?;
 
         // Create a portable package descriptor
         let package = serde_json::json!({
@@ -281,7 +271,7 @@ impl SharingManager {
                 "torrent_info": "document.torrent",
                 "peer_discovery": "mad://discovery.mad-network.org"
             },
-            "created": js_sys::Date::now(),
+            "created": self.clock.now_millis(),
             "version": "1.0"
         });
 
@@ -304,16 +294,18 @@ impl SharingManager {
         let mut hasher = Sha256::new();
         hasher.update(document_hash);
         hasher.update(&self.local_peer_id);
-        hasher.update(&js_sys::Date::now().to_string());
+        hasher.update(self.clock.now_millis().to_string());
         hex::encode(hasher.finalize())
     }
 
-    #[wasm_bindgen]
+    pub fn get_storage_root(&self) -> String {
+        self.storage_root.clone()
+    }
+
     pub fn get_registry_json(&self) -> String {
         serde_json::to_string(&self.registry).unwrap_or_default()
     }
 
-    #[wasm_bindgen]
     pub fn update_peer_reliability(&mut self, peer_id: &str, success: bool) {
         if let Some(peer) = self.registry.peers.get_mut(peer_id) {
             if success {
@@ -321,7 +313,7 @@ impl SharingManager {
             } else {
                 peer.reliability_score = (peer.reliability_score * 0.9).max(0.0);
             }
-            peer.last_seen = js_sys::Date::now() as u64;
+            peer.last_seen = self.clock.now_millis();
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,386 @@
+use crate::error::{MadError, MadResult};
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// BM25 parameters
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+// Characters of context kept either side of the first match in a snippet
+const SNIPPET_CONTEXT: usize = 60;
+const SNIPPET_LENGTH: usize = 240;
+
+// Inverted index over content item text. Document IDs are positions in the
+// document's content item list; postings are keyed by stemmed, lowercased term.
+#[derive(Serialize, Deserialize, Default)]
+pub struct TextIndex {
+    postings: BTreeMap<String, Vec<Posting>>,
+    doc_lengths: Vec<u32>,
+    total_length: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Posting {
+    doc: u32,
+    positions: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TextSearchHit {
+    pub id: String,
+    pub content_type: String,
+    pub score: f32,
+    pub snippet: String,
+    pub highlights: Vec<TextSpan>,
+}
+
+// Character offsets into the item's text_content, end exclusive
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TextSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+pub struct TextMatch {
+    pub doc: usize,
+    pub score: f32,
+    // Matched token ranges (first, last position), for highlighting
+    pub tokens: Vec<(u32, u32)>,
+}
+
+struct Token {
+    term: String,
+    start: usize,
+    end: usize,
+}
+
+enum Clause {
+    Term(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+impl TextIndex {
+    pub fn new() -> TextIndex {
+        TextIndex::default()
+    }
+
+    pub fn build<'a>(texts: impl IntoIterator<Item = &'a str>) -> TextIndex {
+        let mut index = TextIndex::new();
+        for text in texts {
+            index.insert(text);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.doc_lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.doc_lengths.is_empty()
+    }
+
+    pub fn check_len(&self, documents: usize) -> MadResult<()> {
+        if self.len() != documents {
+            return Err(MadError::IntegrityFailure(format!(
+                "text index covers {} content items but the document has {}",
+                self.len(),
+                documents
+            )));
+        }
+        // Postings must point at indexed documents, or scoring would panic
+        for (term, postings) in &self.postings {
+            if let Some(posting) = postings.iter().find(|posting| posting.doc as usize >= documents) {
+                return Err(MadError::IntegrityFailure(format!(
+                    "text index posting for '{}' refers to content item {} but the document has {}",
+                    term,
+                    posting.doc,
+                    documents
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        let doc = self.doc_lengths.len() as u32;
+        let tokens = tokenize(text);
+
+        let mut positions: HashMap<String, Vec<u32>> = HashMap::new();
+        for (position, token) in tokens.iter().enumerate() {
+            positions.entry(token.term.clone()).or_default().push(position as u32);
+        }
+        for (term, positions) in positions {
+            self.postings.entry(term).or_default().push(Posting { doc, positions });
+        }
+
+        self.doc_lengths.push(tokens.len() as u32);
+        self.total_length += tokens.len() as u64;
+    }
+
+    // Query syntax: bare words are OR-ed together, "quoted phrases" must
+    // appear in order, and a trailing * matches any term with that prefix.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<TextMatch> {
+        let mut matches: HashMap<usize, TextMatch> = HashMap::new();
+
+        for clause in parse_query(query) {
+            let hits = match &clause {
+                Clause::Term(term) => self.term_hits(term),
+                Clause::Prefix(prefix) => self.postings.range(prefix.clone()..)
+                    .take_while(|(term, _)| term.starts_with(prefix.as_str()))
+                    .flat_map(|(term, _)| self.term_hits(term))
+                    .collect(),
+                Clause::Phrase(terms) => self.phrase_hits(terms),
+            };
+
+            for hit in hits {
+                let entry = matches.entry(hit.doc).or_insert_with(|| TextMatch {
+                    doc: hit.doc,
+                    score: 0.0,
+                    tokens: Vec::new(),
+                });
+                entry.score += hit.score;
+                entry.tokens.extend(hit.tokens);
+            }
+        }
+
+        let mut results: Vec<TextMatch> = matches.into_values().collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.doc.cmp(&b.doc)));
+        results.truncate(limit);
+        results
+    }
+
+    fn term_hits(&self, term: &str) -> Vec<TextMatch> {
+        let Some(postings) = self.postings.get(term) else {
+            return Vec::new();
+        };
+
+        let idf = self.idf(postings.len());
+        postings.iter()
+            .map(|posting| {
+                let score = idf * self.term_weight(posting.positions.len(), posting.doc);
+                let tokens = posting.positions.iter().map(|&p| (p, p)).collect();
+                TextMatch { doc: posting.doc as usize, score, tokens }
+            })
+            .collect()
+    }
+
+    fn phrase_hits(&self, terms: &[String]) -> Vec<TextMatch> {
+        let Some(lists) = terms.iter().map(|t| self.postings.get(t)).collect::<Option<Vec<_>>>() else {
+            return Vec::new();
+        };
+        let idf: f32 = lists.iter().map(|postings| self.idf(postings.len())).sum();
+        let length = terms.len() as u32 - 1;
+
+        lists[0].iter()
+            .filter_map(|first| {
+                let rest: Vec<&Posting> = lists[1..].iter()
+                    .map(|postings| {
+                        let i = postings.binary_search_by_key(&first.doc, |p| p.doc).ok()?;
+                        Some(&postings[i])
+                    })
+                    .collect::<Option<_>>()?;
+
+                let starts: Vec<u32> = first.positions.iter()
+                    .copied()
+                    .filter(|&start| {
+                        rest.iter().enumerate().all(|(i, posting)| {
+                            posting.positions.binary_search(&(start + i as u32 + 1)).is_ok()
+                        })
+                    })
+                    .collect();
+                if starts.is_empty() {
+                    return None;
+                }
+
+                let score = idf * self.term_weight(starts.len(), first.doc);
+                let tokens = starts.iter().map(|&start| (start, start + length)).collect();
+                Some(TextMatch { doc: first.doc as usize, score, tokens })
+            })
+            .collect()
+    }
+
+    fn idf(&self, doc_freq: usize) -> f32 {
+        let n = self.len() as f32;
+        let df = doc_freq as f32;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    fn term_weight(&self, term_freq: usize, doc: u32) -> f32 {
+        let tf = term_freq as f32;
+        let avg_length = (self.total_length as f32 / self.len().max(1) as f32).max(1.0);
+        let length = self.doc_lengths[doc as usize] as f32;
+        tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / avg_length))
+    }
+}
+
+// Build a snippet around the first match; matches are wrapped in ** and
+// returned as character offsets into the full text.
+pub fn highlight(text: &str, matched_tokens: &[(u32, u32)]) -> (String, Vec<TextSpan>) {
+    let tokens = tokenize(text);
+    let mut spans: Vec<TextSpan> = matched_tokens.iter()
+        .filter_map(|&(first, last)| {
+            let start = tokens.get(first as usize)?.start;
+            let end = tokens.get(last as usize)?.end;
+            Some(TextSpan { start, end })
+        })
+        .collect();
+    spans.sort_by_key(|span| span.start);
+    spans.dedup_by(|next, current| {
+        if next.start <= current.end {
+            current.end = current.end.max(next.end);
+            true
+        } else {
+            false
+        }
+    });
+
+    let chars: Vec<char> = text.chars().collect();
+    let window_start = spans.first()
+        .map(|span| span.start.saturating_sub(SNIPPET_CONTEXT))
+        .unwrap_or(0);
+    let window_end = (window_start + SNIPPET_LENGTH).min(chars.len());
+
+    let mut snippet = String::new();
+    if window_start > 0 {
+        snippet.push('…');
+    }
+    let mut position = window_start;
+    for span in spans.iter().filter(|span| span.start >= window_start && span.end <= window_end) {
+        snippet.extend(&chars[position..span.start]);
+        snippet.push_str("**");
+        snippet.extend(&chars[span.start..span.end]);
+        snippet.push_str("**");
+        position = span.end;
+    }
+    snippet.extend(&chars[position..window_end]);
+    if window_end < chars.len() {
+        snippet.push('…');
+    }
+
+    (snippet.trim().to_string(), spans)
+}
+
+// A snippet of `text` around its best match for `query`, or its opening when
+// there is no query or nothing matches
+pub fn excerpt(text: &str, query: Option<&str>) -> String {
+    let tokens = query
+        .and_then(|query| TextIndex::build([text]).search(query, 1).pop())
+        .map(|found| found.tokens)
+        .unwrap_or_default();
+    highlight(text, &tokens).0
+}
+
+fn parse_query(query: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    let mut rest = query;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+
+        if let Some(quoted) = rest.strip_prefix('"') {
+            // An unterminated quote runs to the end of the query
+            let (phrase, after) = quoted.split_once('"').unwrap_or((quoted, ""));
+            let terms: Vec<String> = tokenize(phrase).into_iter().map(|t| t.term).collect();
+            match terms.len() {
+                0 => {}
+                1 => clauses.push(Clause::Term(terms[0].clone())),
+                _ => clauses.push(Clause::Phrase(terms)),
+            }
+            rest = after;
+            continue;
+        }
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        rest = &rest[end..];
+
+        match word.strip_suffix('*') {
+            Some(prefix) => {
+                let prefix: String = prefix.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect();
+                if !prefix.is_empty() {
+                    clauses.push(Clause::Prefix(prefix));
+                }
+            }
+            None => clauses.extend(tokenize(word).into_iter().map(|t| Clause::Term(t.term))),
+        }
+    }
+
+    clauses
+}
+
+// The stemmed, lowercased terms of a text, in order
+pub(crate) fn terms(text: &str) -> Vec<String> {
+    tokenize(text).into_iter().map(|token| token.term).collect()
+}
+
+// Split on anything that isn't alphanumeric; offsets are in characters
+fn tokenize(text: &str) -> Vec<Token> {
+    let stemmer = Stemmer::create(Algorithm::English);
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+
+    for (position, c) in text.chars().chain(std::iter::once(' ')).enumerate() {
+        if c.is_alphanumeric() {
+            if current.is_empty() {
+                start = position;
+            }
+            current.extend(c.to_lowercase());
+        } else if !current.is_empty() {
+            tokens.push(Token {
+                term: stemmer.stem(&current).into_owned(),
+                start,
+                end: position,
+            });
+            current.clear();
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(index: &TextIndex, query: &str) -> Vec<usize> {
+        index.search(query, 10).iter().map(|m| m.doc).collect()
+    }
+
+    #[test]
+    fn ranks_by_bm25() {
+        let index = TextIndex::build([
+            "graphs and vectors",
+            "graph databases store graphs of graph nodes",
+            "nothing relevant here",
+        ]);
+        assert_eq!(ranked(&index, "graph"), vec![1, 0]);
+        assert!(ranked(&index, "missing").is_empty());
+    }
+
+    #[test]
+    fn stems_and_matches_phrases_and_prefixes() {
+        let index = TextIndex::build(["the runner was running", "run fast", "fast run"]);
+        assert_eq!(ranked(&index, "runs").len(), 3);
+        assert_eq!(ranked(&index, "\"run fast\""), vec![1]);
+        assert_eq!(ranked(&index, "runn*"), vec![0]);
+    }
+
+    #[test]
+    fn check_len_rejects_mismatched_indexes() {
+        let index = TextIndex::build(["one", "two"]);
+        assert!(index.check_len(2).is_ok());
+        assert!(index.check_len(3).is_err());
+
+        let mut corrupt: serde_json::Value = serde_json::to_value(&index).unwrap();
+        corrupt["postings"]["one"][0]["doc"] = serde_json::json!(7);
+        let corrupt: TextIndex = serde_json::from_value(corrupt).unwrap();
+        assert!(matches!(corrupt.check_len(2), Err(MadError::IntegrityFailure(_))));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::error::{MadError, MadResult};
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+// Tuning knobs for the HNSW graph. `ef_search` is the default recall/speed
+// trade-off at query time and can be overridden per query.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct HnswParams {
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        HnswParams {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+// Hierarchical navigable small world graph over a document's embeddings.
+// Node IDs are positions in the document's vector list. Only the graph is
+// serialized; the normalized vectors are rebuilt with `attach` on load.
+#[derive(Serialize, Deserialize)]
+pub struct HnswIndex {
+    params: HnswParams,
+    // links[node][layer] = neighbour node IDs
+    links: Vec<Vec<Vec<u32>>>,
+    entry_point: Option<u32>,
+    max_level: usize,
+    #[serde(skip)]
+    vectors: Vec<Vec<f32>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: u32,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl HnswIndex {
+    pub fn new(params: HnswParams) -> HnswIndex {
+        HnswIndex {
+            params: HnswParams {
+                m: params.m.max(2),
+                ef_construction: params.ef_construction.max(1),
+                ef_search: params.ef_search.max(1),
+            },
+            links: Vec::new(),
+            entry_point: None,
+            max_level: 0,
+            vectors: Vec::new(),
+        }
+    }
+
+    pub fn build<'a>(params: HnswParams, embeddings: impl IntoIterator<Item = &'a [f32]>) -> HnswIndex {
+        let mut index = HnswIndex::new(params);
+        for embedding in embeddings {
+            index.insert(embedding);
+        }
+        index
+    }
+
+    pub fn params(&self) -> HnswParams {
+        self.params
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    // Restore the normalized vectors after deserializing the graph
+    pub fn attach<'a>(&mut self, embeddings: impl IntoIterator<Item = &'a [f32]>) -> MadResult<()> {
+        self.vectors = embeddings.into_iter().map(normalize).collect();
+        if self.vectors.len() != self.links.len() {
+            return Err(MadError::IntegrityFailure(format!(
+                "vector index covers {} embeddings but the document has {}",
+                self.links.len(),
+                self.vectors.len()
+            )));
+        }
+        self.check_links()
+    }
+
+    // A loaded graph must only point at nodes and layers that exist, or
+    // search would index out of bounds
+    fn check_links(&self) -> MadResult<()> {
+        let corrupt = |message: String| Err(MadError::IntegrityFailure(format!("vector index: {}", message)));
+        let len = self.links.len();
+
+        match self.entry_point {
+            None if len > 0 => return corrupt("no entry point".to_string()),
+            Some(entry) if entry as usize >= len => {
+                return corrupt(format!("entry point {} is out of range for {} nodes", entry, len));
+            }
+            Some(entry) if self.links[entry as usize].len() != self.max_level + 1 => {
+                return corrupt(format!("entry point has {} layers but max level is {}", self.links[entry as usize].len(), self.max_level));
+            }
+            _ => {}
+        }
+
+        for (node, layers) in self.links.iter().enumerate() {
+            if layers.is_empty() || layers.len() > self.max_level + 1 {
+                return corrupt(format!("node {} has {} layers", node, layers.len()));
+            }
+            for (layer, neighbours) in layers.iter().enumerate() {
+                for &neighbour in neighbours {
+                    // Neighbours on a layer must reach that layer themselves
+                    if self.links.get(neighbour as usize).is_none_or(|n| n.len() <= layer) {
+                        return corrupt(format!("node {} links to missing node {} on layer {}", node, neighbour, layer));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, embedding: &[f32]) {
+        let node = self.links.len() as u32;
+        let level = self.level_for(node);
+        self.vectors.push(normalize(embedding));
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(mut entry) = self.entry_point else {
+            self.entry_point = Some(node);
+            self.max_level = level;
+            return;
+        };
+
+        let query = self.vectors[node as usize].clone();
+        for layer in (level + 1..=self.max_level).rev() {
+            entry = self.greedy_closest(&query, entry, layer);
+        }
+
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(&query, entry, self.params.ef_construction, layer);
+            let max_links = self.max_links(layer);
+            let neighbours: Vec<u32> = candidates.iter().take(max_links).map(|c| c.node).collect();
+
+            for &neighbour in &neighbours {
+                let links = &mut self.links[neighbour as usize][layer];
+                links.push(node);
+                if links.len() > max_links {
+                    self.prune(neighbour, layer, max_links);
+                }
+            }
+            self.links[node as usize][layer] = neighbours;
+
+            if let Some(closest) = candidates.first() {
+                entry = closest.node;
+            }
+        }
+
+        if level > self.max_level {
+            self.entry_point = Some(node);
+            self.max_level = level;
+        }
+    }
+
+    // Returns (node, cosine similarity) pairs, best first. `ef` defaults to
+    // the index's ef_search; larger values raise recall at the cost of speed.
+    pub fn search(&self, query: &[f32], top_k: usize, ef: Option<usize>) -> Vec<(usize, f32)> {
+        let Some(mut entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if top_k == 0 {
+            return Vec::new();
+        }
+
+        let query = normalize(query);
+        for layer in (1..=self.max_level).rev() {
+            entry = self.greedy_closest(&query, entry, layer);
+        }
+
+        let ef = ef.unwrap_or(self.params.ef_search).max(top_k);
+        self.search_layer(&query, entry, ef, 0)
+            .into_iter()
+            .take(top_k)
+            .map(|c| (c.node as usize, 1.0 - c.distance))
+            .collect()
+    }
+
+    // Exact cosine similarity against an indexed vector; `query` must be normalized
+    pub fn similarity(&self, query: &[f32], node: usize) -> f32 {
+        dot(query, &self.vectors[node])
+    }
+
+    fn search_layer(&self, query: &[f32], entry: u32, ef: usize, layer: usize) -> Vec<Candidate> {
+        let start = Candidate { distance: self.distance(query, entry), node: entry };
+        let mut visited = HashSet::from([entry]);
+        let mut candidates = BinaryHeap::from([Reverse(start)]);
+        let mut found = BinaryHeap::from([start]);
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            let furthest = found.peek().map(|c| c.distance).unwrap_or(f32::INFINITY);
+            if current.distance > furthest && found.len() >= ef {
+                break;
+            }
+
+            for &neighbour in self.neighbours(current.node, layer) {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let candidate = Candidate { distance: self.distance(query, neighbour), node: neighbour };
+                let furthest = found.peek().map(|c| c.distance).unwrap_or(f32::INFINITY);
+                if found.len() < ef || candidate.distance < furthest {
+                    candidates.push(Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found.into_sorted_vec()
+    }
+
+    fn greedy_closest(&self, query: &[f32], entry: u32, layer: usize) -> u32 {
+        let mut best = entry;
+        let mut best_distance = self.distance(query, entry);
+        loop {
+            let mut improved = false;
+            for &neighbour in self.neighbours(best, layer) {
+                let distance = self.distance(query, neighbour);
+                if distance < best_distance {
+                    best = neighbour;
+                    best_distance = distance;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return best;
+            }
+        }
+    }
+
+    // Keep the closest `max_links` neighbours of an over-full node
+    fn prune(&mut self, node: u32, layer: usize, max_links: usize) {
+        let base = &self.vectors[node as usize];
+        let mut scored: Vec<Candidate> = self.links[node as usize][layer].iter()
+            .map(|&n| Candidate { distance: 1.0 - dot(base, &self.vectors[n as usize]), node: n })
+            .collect();
+        scored.sort();
+        scored.truncate(max_links);
+        self.links[node as usize][layer] = scored.into_iter().map(|c| c.node).collect();
+    }
+
+    fn neighbours(&self, node: u32, layer: usize) -> &[u32] {
+        self.links[node as usize].get(layer).map(|l| l.as_slice()).unwrap_or(&[])
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 { self.params.m * 2 } else { self.params.m }
+    }
+
+    fn distance(&self, query: &[f32], node: u32) -> f32 {
+        1.0 - dot(query, &self.vectors[node as usize])
+    }
+
+    // Deterministic level assignment so the same vectors always build the same graph
+    fn level_for(&self, node: u32) -> usize {
+        let mut x = (node as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+
+        let uniform = ((x >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level_mult = 1.0 / (self.params.m as f64).ln();
+        ((-uniform.ln() * level_mult) as usize).min(16)
+    }
+}
+
+pub fn normalize(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        vector.to_vec()
+    } else {
+        vector.iter().map(|x| x / norm).collect()
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic pseudo-random vectors
+    fn vectors(count: usize, dimension: usize) -> Vec<Vec<f32>> {
+        let mut state: u32 = 0x9e37_79b9;
+        (0..count).map(|_| {
+            (0..dimension).map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+            }).collect()
+        }).collect()
+    }
+
+    fn build(vectors: &[Vec<f32>]) -> HnswIndex {
+        HnswIndex::build(HnswParams::default(), vectors.iter().map(Vec::as_slice))
+    }
+
+    #[test]
+    fn finds_each_vector_as_its_own_nearest_neighbour() {
+        let vectors = vectors(200, 16);
+        let index = build(&vectors);
+        for (node, vector) in vectors.iter().enumerate() {
+            let hits = index.search(&normalize(vector), 1, None);
+            assert_eq!(hits[0].0, node);
+            assert!((hits[0].1 - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn survives_serialization() {
+        let vectors = vectors(50, 8);
+        let index = build(&vectors);
+        let mut loaded: HnswIndex = serde_json::from_str(&serde_json::to_string(&index).unwrap()).unwrap();
+        loaded.attach(vectors.iter().map(Vec::as_slice)).unwrap();
+        let query = normalize(&vectors[7]);
+        assert_eq!(loaded.search(&query, 5, None), index.search(&query, 5, None));
+    }
+
+    #[test]
+    fn attach_rejects_corrupt_graphs() {
+        let vectors = vectors(20, 4);
+        let json = serde_json::to_value(build(&vectors)).unwrap();
+        let attach = |json: serde_json::Value| {
+            let mut index: HnswIndex = serde_json::from_value(json).unwrap();
+            index.attach(vectors.iter().map(Vec::as_slice))
+        };
+
+        let mut bad_neighbour = json.clone();
+        bad_neighbour["links"][0][0] = serde_json::json!([99]);
+        assert!(matches!(attach(bad_neighbour), Err(MadError::IntegrityFailure(_))));
+
+        let mut bad_entry = json.clone();
+        bad_entry["entry_point"] = serde_json::json!(20);
+        assert!(matches!(attach(bad_entry), Err(MadError::IntegrityFailure(_))));
+
+        let mut bad_level = json.clone();
+        bad_level["max_level"] = serde_json::json!(40);
+        assert!(matches!(attach(bad_level), Err(MadError::IntegrityFailure(_))));
+
+        let mut index: HnswIndex = serde_json::from_value(json).unwrap();
+        assert!(index.attach(vectors[1..].iter().map(Vec::as_slice)).is_err());
+    }
+}
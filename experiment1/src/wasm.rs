@@ -0,0 +1,518 @@
+// wasm-bindgen layer: thin JS-facing wrappers around the native core types.
+// Every method forwards to the core and turns MadError into a JS string error.
+use wasm_bindgen::prelude::*;
+use crate::graph_db::parse_properties;
+use crate::{ChunkOptions, ChunkStrategy, GraphDatabase, HashedEmbeddingProvider, HnswParams, HybridSearchOptions, MadBuilder, MadDocument, MadError, McpLimits, McpServer, MetadataFilter, Quantization, SharingManager, VectorSearchOptions};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(s: &str);
+}
+
+macro_rules! console_log {
+    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
+}
+
+impl From<MadError> for JsValue {
+    fn from(e: MadError) -> JsValue {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+#[wasm_bindgen(js_name = MadDocument)]
+pub struct WasmMadDocument {
+    inner: MadDocument,
+}
+
+#[wasm_bindgen(js_class = MadDocument)]
+impl WasmMadDocument {
+    #[wasm_bindgen(constructor)]
+    pub fn new(title: &str, author: &str) -> WasmMadDocument {
+        WasmMadDocument { inner: MadDocument::new(title, author) }
+    }
+
+    pub fn init_databases(&mut self) -> Result<(), JsValue> {
+        self.inner.init_databases().map_err(JsValue::from)
+    }
+
+    pub fn add_content(&mut self, content_type: &str, data: &[u8], text_content: &str) -> Result<String, JsValue> {
+        self.inner.add_content(content_type, data, text_content).map_err(JsValue::from)
+    }
+
+    pub fn add_vector_embedding(&mut self, content_id: &str, content: &str, embedding: &[f32]) -> Result<(), JsValue> {
+        self.inner.add_vector_embedding(content_id, content, embedding).map_err(JsValue::from)
+    }
+
+    pub fn add_vector_embedding_with_metadata(&mut self, content_id: &str, content: &str, embedding: &[f32], metadata_json: &str) -> Result<(), JsValue> {
+        let metadata = parse_properties(metadata_json).map_err(JsValue::from)?;
+        self.inner.add_vector_embedding_with_metadata(content_id, content, embedding, metadata).map_err(JsValue::from)
+    }
+
+    pub fn add_graph_node(&mut self, id: &str, label: &str, properties_json: &str) -> Result<(), JsValue> {
+        self.inner.add_graph_node(id, label, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn add_graph_edge(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> Result<String, JsValue> {
+        self.inner.add_graph_edge(from, to, relationship, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn merge_graph_node(&mut self, id: &str, label: &str, properties_json: &str) -> Result<bool, JsValue> {
+        self.inner.merge_graph_node(id, label, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn update_graph_node(&mut self, id: &str, properties_json: &str) -> Result<(), JsValue> {
+        self.inner.update_graph_node(id, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn remove_graph_node(&mut self, id: &str) -> Result<usize, JsValue> {
+        self.inner.remove_graph_node(id).map_err(JsValue::from)
+    }
+
+    pub fn remove_graph_edge(&mut self, edge_id: &str) -> Result<(), JsValue> {
+        self.inner.remove_graph_edge(edge_id).map_err(JsValue::from)
+    }
+
+    pub fn query_graph(&self, start_node: &str, max_depth: usize, relationship_filter: Option<String>) -> String {
+        self.inner.query_graph(start_node, max_depth, relationship_filter.as_deref())
+    }
+
+    pub fn find_path(&self, start: &str, end: &str, relationship_filter: Option<String>) -> String {
+        self.inner.find_path(start, end, relationship_filter.as_deref())
+    }
+
+    pub fn query_cypher(&self, query: &str) -> Result<String, JsValue> {
+        self.inner.query_cypher(query).map_err(JsValue::from)
+    }
+
+    pub fn search_content(&self, query: &str) -> Result<String, JsValue> {
+        self.inner.search_content(query).map_err(JsValue::from)
+    }
+
+    pub fn search_content_page(&self, query: &str, cursor: Option<String>, limit: usize) -> Result<String, JsValue> {
+        let page = self.inner.search_text_page(query, cursor.as_deref(), limit)?;
+        serde_json::to_string(&page).map_err(|e| JsValue::from(MadError::from(e)))
+    }
+
+    pub fn vector_similarity_search(&self, query_embedding: &[f32], top_k: usize) -> Result<String, JsValue> {
+        self.inner.vector_similarity_search(query_embedding, top_k).map_err(JsValue::from)
+    }
+
+    pub fn vector_similarity_search_filtered(&self, query_embedding: &[f32], top_k: usize, filter: &str) -> Result<String, JsValue> {
+        let filter = MetadataFilter::parse(filter).map_err(JsValue::from)?;
+        self.inner.vector_similarity_search_filtered(query_embedding, top_k, &filter, None).map_err(JsValue::from)
+    }
+
+    pub fn vector_similarity_search_with_ef(&self, query_embedding: &[f32], top_k: usize, ef: Option<usize>) -> Result<String, JsValue> {
+        self.inner.vector_similarity_search_with_ef(query_embedding, top_k, ef).map_err(JsValue::from)
+    }
+
+    // Text query embedded with the offline hashed encoder; returns ranked passages as JSON
+    pub fn semantic_search(&self, query: &str, top_k: usize) -> Result<String, JsValue> {
+        let options = VectorSearchOptions { top_k, ..VectorSearchOptions::default() };
+        let hits = self.inner.semantic_search(query, &options, None).map_err(JsValue::from)?;
+        serde_json::to_string(&hits).map_err(|e| JsValue::from(MadError::from(e)))
+    }
+
+    pub fn hybrid_search(&self, query: &str, query_embedding: Option<Vec<f32>>, top_k: usize) -> Result<String, JsValue> {
+        let options = HybridSearchOptions { top_k, ..HybridSearchOptions::default() };
+        let hits = self.inner.hybrid_search(query, query_embedding.as_deref(), &options).map_err(JsValue::from)?;
+        serde_json::to_string(&hits).map_err(|e| JsValue::from(MadError::from(e)))
+    }
+
+    pub fn build_vector_index(&mut self, m: usize, ef_construction: usize, ef_search: usize) {
+        self.inner.build_vector_index(HnswParams { m, ef_construction, ef_search });
+    }
+
+    pub fn get_metadata(&self) -> String {
+        self.inner.get_metadata()
+    }
+
+    pub fn export_markdown(&self, include_metadata: bool) -> String {
+        self.inner.export_markdown(include_metadata)
+    }
+
+    pub fn export_html(&self, include_metadata: bool) -> String {
+        self.inner.export_html(include_metadata)
+    }
+
+    pub fn export_json(&self) -> Result<String, JsValue> {
+        self.inner.export_json().map_err(JsValue::from)
+    }
+
+    pub fn from_adam_json(adam_json: &str) -> Result<WasmMadDocument, JsValue> {
+        MadDocument::from_adam_json(adam_json)
+            .map(|inner| WasmMadDocument { inner })
+            .map_err(JsValue::from)
+    }
+
+    pub fn to_adam_json(&self) -> Result<String, JsValue> {
+        self.inner.to_adam_json().map_err(JsValue::from)
+    }
+
+    pub fn calculate_content_hash(&mut self) -> String {
+        self.inner.calculate_content_hash()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        self.inner.to_bytes().map_err(JsValue::from)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmMadDocument, JsValue> {
+        MadDocument::from_bytes(bytes)
+            .map(|inner| WasmMadDocument { inner })
+            .map_err(JsValue::from)
+    }
+}
+
+#[wasm_bindgen(js_name = GraphDatabase)]
+pub struct WasmGraphDatabase {
+    inner: GraphDatabase,
+}
+
+#[wasm_bindgen(js_class = GraphDatabase)]
+impl WasmGraphDatabase {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmGraphDatabase {
+        WasmGraphDatabase { inner: GraphDatabase::new() }
+    }
+
+    pub fn add_node(&mut self, id: &str, label: &str, properties_json: &str) -> Result<(), JsValue> {
+        self.inner.add_node(id, label, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn add_edge(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> Result<String, JsValue> {
+        self.inner.add_edge(from, to, relationship, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn add_edge_with_id(&mut self, edge_id: &str, from: &str, to: &str, relationship: &str, properties_json: &str) -> Result<String, JsValue> {
+        self.inner.add_edge_with_id(edge_id, from, to, relationship, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn merge_node(&mut self, id: &str, label: &str, properties_json: &str) -> Result<bool, JsValue> {
+        self.inner.merge_node(id, label, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn update_node_properties(&mut self, id: &str, properties_json: &str) -> Result<(), JsValue> {
+        self.inner.update_node_properties(id, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn remove_node(&mut self, id: &str) -> Result<usize, JsValue> {
+        self.inner.remove_node(id).map_err(JsValue::from)
+    }
+
+    pub fn remove_edge(&mut self, edge_id: &str) -> Result<(), JsValue> {
+        self.inner.remove_edge(edge_id).map_err(JsValue::from)
+    }
+
+    pub fn get_node(&self, id: &str) -> Option<String> {
+        self.inner.get_node(id)
+    }
+
+    pub fn get_neighbors(&self, node_id: &str, relationship_filter: Option<String>) -> String {
+        self.inner.get_neighbors(node_id, relationship_filter.as_deref())
+    }
+
+    pub fn traverse(&self, start_node: &str, max_depth: usize, relationship_filter: Option<String>) -> String {
+        self.inner.traverse(start_node, max_depth, relationship_filter.as_deref())
+    }
+
+    pub fn find_shortest_path(&self, start: &str, end: &str, relationship_filter: Option<String>) -> String {
+        self.inner.find_shortest_path(start, end, relationship_filter.as_deref())
+    }
+
+    pub fn query_by_properties(&self, node_properties: &str, edge_properties: &str) -> Result<String, JsValue> {
+        self.inner.query_by_properties(node_properties, edge_properties).map_err(JsValue::from)
+    }
+
+    // JSON array of nodes, in ID order
+    pub fn nodes_by_label(&self, label: &str) -> Result<String, JsValue> {
+        let nodes: Vec<_> = self.inner.nodes_by_label(label).collect();
+        serde_json::to_string(&nodes).map_err(|e| JsValue::from(MadError::from(e)))
+    }
+
+    pub fn query_cypher(&self, query: &str) -> Result<String, JsValue> {
+        self.inner.query_cypher(query).map_err(JsValue::from)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.inner.node_count()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.inner.edge_count()
+    }
+
+    pub fn get_statistics(&self) -> String {
+        self.inner.get_statistics()
+    }
+
+    pub fn export_json(&self) -> String {
+        self.inner.export_json()
+    }
+
+    pub fn import_json(graph_json: &str) -> Result<WasmGraphDatabase, JsValue> {
+        GraphDatabase::import_json(graph_json)
+            .map(|inner| WasmGraphDatabase { inner })
+            .map_err(JsValue::from)
+    }
+
+    pub fn export_cypher(&self) -> Result<String, JsValue> {
+        self.inner.export_cypher().map_err(JsValue::from)
+    }
+
+    pub fn export_cypher_batched(&self, batch_size: usize) -> Result<String, JsValue> {
+        self.inner.export_cypher_batched(batch_size).map_err(JsValue::from)
+    }
+
+    // JSON array of {query, parameters} for a database driver
+    pub fn cypher_batches(&self, batch_size: usize) -> Result<String, JsValue> {
+        let batches = self.inner.cypher_batches(batch_size)?;
+        serde_json::to_string(&batches)
+            .map_err(|e| JsValue::from(MadError::from(e)))
+    }
+
+    pub fn import_cypher(script: &str) -> Result<WasmGraphDatabase, JsValue> {
+        GraphDatabase::import_cypher(script)
+            .map(|inner| WasmGraphDatabase { inner })
+            .map_err(JsValue::from)
+    }
+}
+
+impl Default for WasmGraphDatabase {
+    fn default() -> Self {
+        WasmGraphDatabase::new()
+    }
+}
+
+#[wasm_bindgen(js_name = MadBuilder)]
+pub struct WasmMadBuilder {
+    inner: MadBuilder,
+}
+
+#[wasm_bindgen(js_class = MadBuilder)]
+impl WasmMadBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(title: &str, author: &str) -> WasmMadBuilder {
+        WasmMadBuilder { inner: MadBuilder::new(title, author) }
+    }
+
+    pub fn add_text_content(&mut self, text: &str, content_type: &str) -> Result<String, JsValue> {
+        self.inner.add_text_content(text, content_type).map_err(JsValue::from)
+    }
+
+    pub fn add_binary_content(&mut self, data: &[u8], content_type: &str, description: &str) -> Result<String, JsValue> {
+        self.inner.add_binary_content(data, content_type, description).map_err(JsValue::from)
+    }
+
+    pub fn add_html_content(&mut self, html: &str) -> Result<String, JsValue> {
+        self.inner.add_html_content(html).map_err(JsValue::from)
+    }
+
+    pub fn add_markdown_content(&mut self, markdown: &str) -> Result<String, JsValue> {
+        self.inner.add_markdown_content(markdown).map_err(JsValue::from)
+    }
+
+    pub fn add_vector_embedding(&mut self, content_id: &str, embedding: &[f32]) -> Result<(), JsValue> {
+        self.inner.add_vector_embedding(content_id, embedding).map_err(JsValue::from)
+    }
+
+    // Offline feature-hashed embeddings; call embed_content to embed every chunk
+    pub fn use_hashed_embeddings(&mut self, dimension: usize) -> Result<(), JsValue> {
+        self.inner.set_embedding_provider(Box::new(HashedEmbeddingProvider::new(dimension))).map_err(JsValue::from)
+    }
+
+    pub fn set_embedding_model(&mut self, model: &str, dimension: usize) -> Result<(), JsValue> {
+        self.inner.set_embedding_model(model, dimension).map_err(JsValue::from)
+    }
+
+    pub fn embed_content(&mut self) -> Result<usize, JsValue> {
+        self.inner.embed_content().map_err(JsValue::from)
+    }
+
+    // `strategy` is "tokens", "sentences" or "markdown"; returns the chunk IDs as a JSON array
+    pub fn chunk_content(&mut self, content_id: &str, strategy: &str, max_tokens: usize, overlap: usize) -> Result<String, JsValue> {
+        let strategy = ChunkStrategy::from_name(strategy).map_err(JsValue::from)?;
+        let options = ChunkOptions { strategy, max_tokens, overlap };
+        let ids = self.inner.chunk_content(content_id, &options).map_err(JsValue::from)?;
+        serde_json::to_string(&ids).map_err(|e| JsValue::from(MadError::from(e)))
+    }
+
+    pub fn add_vector_embedding_with_metadata(&mut self, content_id: &str, embedding: &[f32], metadata_json: &str) -> Result<(), JsValue> {
+        let metadata = parse_properties(metadata_json).map_err(JsValue::from)?;
+        self.inner.add_vector_embedding_with_metadata(content_id, embedding, metadata).map_err(JsValue::from)
+    }
+
+    pub fn create_entity(&mut self, id: &str, label: &str, properties_json: &str) -> Result<(), JsValue> {
+        self.inner.create_entity(id, label, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn create_relationship(&mut self, from: &str, to: &str, relationship: &str, properties_json: &str) -> Result<String, JsValue> {
+        self.inner.create_relationship(from, to, relationship, properties_json).map_err(JsValue::from)
+    }
+
+    pub fn auto_extract_entities(&mut self, content_id: &str, entity_types: &str) -> Result<String, JsValue> {
+        self.inner.auto_extract_entities(content_id, entity_types).map_err(JsValue::from)
+    }
+
+    pub fn add_citation(&mut self, from_content: &str, to_content: &str, citation_type: &str) -> Result<String, JsValue> {
+        self.inner.add_citation(from_content, to_content, citation_type).map_err(JsValue::from)
+    }
+
+    pub fn build(&mut self) -> Result<(), JsValue> {
+        self.inner.build().map_err(JsValue::from)
+    }
+
+    pub fn create_fresh_document(&self) -> Result<WasmMadDocument, JsValue> {
+        self.inner.create_fresh_document()
+            .map(|inner| WasmMadDocument { inner })
+            .map_err(JsValue::from)
+    }
+
+    pub fn get_document(self) -> WasmMadDocument {
+        WasmMadDocument { inner: self.inner.get_document() }
+    }
+
+    pub fn export_manifest(&self) -> String {
+        self.inner.export_manifest()
+    }
+
+    pub fn get_content_items_json(&self) -> String {
+        self.inner.get_content_items_json()
+    }
+
+    pub fn get_embeddings_json(&self) -> String {
+        self.inner.get_embeddings_json()
+    }
+
+    pub fn get_entities_json(&self) -> String {
+        self.inner.get_entities_json()
+    }
+
+    pub fn export_package(&self) -> Result<Vec<u8>, JsValue> {
+        self.inner.export_package().map_err(JsValue::from)
+    }
+
+    pub fn set_vector_index_params(&mut self, m: usize, ef_construction: usize, ef_search: usize) {
+        self.inner.set_vector_index_params(HnswParams { m, ef_construction, ef_search });
+    }
+
+    // `quantization` is "int8", "binary" or "none"
+    pub fn set_vector_quantization(&mut self, quantization: &str, keep_full_precision: bool) -> Result<(), JsValue> {
+        let quantization = match quantization {
+            "none" => None,
+            name => Some(Quantization::from_name(name).map_err(JsValue::from)?),
+        };
+        self.inner.set_vector_quantization(quantization, keep_full_precision);
+        Ok(())
+    }
+}
+
+#[wasm_bindgen(js_name = SharingManager)]
+pub struct WasmSharingManager {
+    inner: SharingManager,
+}
+
+#[wasm_bindgen(js_class = SharingManager)]
+impl WasmSharingManager {
+    #[wasm_bindgen(constructor)]
+    pub fn new(peer_id: &str, storage_root: &str) -> WasmSharingManager {
+        WasmSharingManager { inner: SharingManager::new(peer_id, storage_root) }
+    }
+
+    pub fn register_document(&mut self, hash: &str, title: &str, author: &str, size_bytes: u64) -> Result<(), JsValue> {
+        self.inner.register_document(hash, title, author, size_bytes).map_err(JsValue::from)
+    }
+
+    pub fn register_peer(&mut self, peer_id: &str, address: &str) -> Result<(), JsValue> {
+        self.inner.register_peer(peer_id, address).map_err(JsValue::from)
+    }
+
+    pub fn create_share_link(&self, document_hash: &str, access_level: &str, expiration_hours: Option<u32>) -> Result<String, JsValue> {
+        self.inner.create_share_link(document_hash, access_level, expiration_hours).map_err(JsValue::from)
+    }
+
+    pub fn parse_share_link(&self, share_link: &str) -> Result<String, JsValue> {
+        self.inner.parse_share_link(share_link).map_err(JsValue::from)
+    }
+
+    pub fn find_document_peers(&self, document_hash: &str) -> String {
+        self.inner.find_document_peers(document_hash)
+    }
+
+    pub fn create_sync_manifest(&self) -> String {
+        self.inner.create_sync_manifest()
+    }
+
+    pub fn generate_ipfs_hash(&self, document_hash: &str) -> String {
+        self.inner.generate_ipfs_hash(document_hash)
+    }
+
+    pub fn create_torrent_info(&self, document_hash: &str) -> Result<String, JsValue> {
+        self.inner.create_torrent_info(document_hash).map_err(JsValue::from)
+    }
+
+    pub fn get_availability_stats(&self, document_hash: &str) -> Result<String, JsValue> {
+        self.inner.get_availability_stats(document_hash).map_err(JsValue::from)
+    }
+
+    pub fn export_portable_package(&self, document_hash: &str) -> Result<String, JsValue> {
+        self.inner.export_portable_package(document_hash).map_err(JsValue::from)
+    }
+
+    pub fn get_storage_root(&self) -> String {
+        self.inner.get_storage_root()
+    }
+
+    pub fn get_registry_json(&self) -> String {
+        self.inner.get_registry_json()
+    }
+
+    pub fn update_peer_reliability(&mut self, peer_id: &str, success: bool) {
+        self.inner.update_peer_reliability(peer_id, success)
+    }
+}
+
+#[wasm_bindgen(js_name = McpServer)]
+pub struct WasmMcpServer {
+    inner: McpServer,
+}
+
+#[wasm_bindgen(js_class = McpServer)]
+impl WasmMcpServer {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmMcpServer {
+        WasmMcpServer { inner: McpServer::new() }
+    }
+
+    pub fn set_document(&mut self, document: WasmMadDocument) {
+        self.inner.set_document(document.inner)
+    }
+
+    pub fn set_limits(&mut self, max_results: usize, max_graph_items: usize, list_page_size: usize) {
+        self.inner.set_limits(McpLimits { max_results, max_graph_items, list_page_size })
+    }
+
+    pub fn handle_request(&self, request_json: &str) -> String {
+        self.inner.handle_request(request_json)
+    }
+
+    pub fn get_tools_json(&self) -> String {
+        self.inner.get_tools_json()
+    }
+
+    pub fn get_resources_json(&self) -> String {
+        self.inner.get_resources_json()
+    }
+}
+
+impl Default for WasmMcpServer {
+    fn default() -> Self {
+        WasmMcpServer::new()
+    }
+}
+
+#[wasm_bindgen(start)]
+pub fn main() {
+    console_log!("MAD Runtime initialized");
+}